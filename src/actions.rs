@@ -1,6 +1,10 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+use crate::schema;
+
 const GENERAL_SYSTEM_PROMPT: &str = r#"
 Your name is Pristine, you are an AI agent that manages issues on a GitHub repository.
 Your goal is to help maintain the state of issues in the repository by creating, updating, and closing issues as needed.
@@ -19,8 +23,9 @@ Your responsibilities include:
 
 You should also:
 - Answer human comments on issues.
-- Detect TODOs in code and map them to issues.
+- Detect TODOs in code (search for them with `search_code`) and map them to issues.
 - Close issues when they are resolved.
+- Review merged pull requests and close the issues they resolve.
 - Prioritize issues based on their importance and urgency.
 - Manage labels on issues to categorize them effectively.
 
@@ -67,6 +72,17 @@ A few rules to follow:
 Actions you can take:
 "#;
 
+const TOOL_CALLING_ADD_ON: &str = r#"
+Your role is to think carefully about the current state and what actions should be taken next.
+Weigh different options and consider the consequences of each action.
+Try to think of the goal you're trying to achieve and how the actions you take will help you get there.
+
+First write your thoughts as plain text, then call the tools matching the actions you want to take.
+You must at least have one sentence of thoughts and call at least one tool.
+Batch actions together when possible, e.g. storing multiple memories at once, or getting multiple issues at once.
+Use up to 10 tool calls in a single output.
+"#;
+
 const THINKING_ADD_ON: &str = r#"
 Your role is to think carefully about the current state and what actions should be taken next. 
 Weigh different options and consider the consequences of each action. 
@@ -89,19 +105,18 @@ Actions:
 ===
 [
     {
-        "StoreOrUpdateMemoryInContext": {
+        "store_or_update_memory_in_context": {
             "key": "My name",
             "value": "Pristine"
         }
     },
     {
-        "StoreOrUpdateMemoryInContext": {
+        "store_or_update_memory_in_context": {
             "key": "My goal",
             "value": "Manage issues, detect bugs, detect missing documentation, and creating relevant issues."
         }
     },
-    "ListAllFiles",
-
+    "list_all_files"
 ]
 ===
 
@@ -119,8 +134,59 @@ pub enum Actions {
         user_prompt: String,
     },
     ListAllFiles,
+    ListFileTree,
     ReadASingleFile {
         path: String,
+        /// First line to return, 1-based and inclusive.
+        #[serde(default)]
+        start_line: Option<usize>,
+        /// Last line to return, inclusive.
+        #[serde(default)]
+        end_line: Option<usize>,
+        #[serde(default)]
+        max_bytes: Option<usize>,
+    },
+    OutlineFile {
+        path: String,
+    },
+    SearchCode {
+        /// Regular expression matched against each line.
+        pattern: String,
+        #[serde(default)]
+        glob: Option<String>,
+        #[serde(default)]
+        context_lines: Option<usize>,
+        #[serde(default)]
+        max_results: Option<usize>,
+    },
+    GitLog {
+        /// A revision such as `main`, or a range such as `v1.0..HEAD`.
+        #[serde(default)]
+        range: Option<String>,
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        max_count: Option<usize>,
+    },
+    GitShow {
+        revision: String,
+        #[serde(default)]
+        stat_only: Option<bool>,
+    },
+    GitDiff {
+        from: String,
+        to: String,
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        stat_only: Option<bool>,
+    },
+    GitBlame {
+        path: String,
+        #[serde(default)]
+        start_line: Option<usize>,
+        #[serde(default)]
+        end_line: Option<usize>,
     },
     RunCommand {
         command: String,
@@ -167,6 +233,22 @@ pub enum Actions {
         issue_number: u64,
         title: String,
     },
+    GithubListPullRequests {
+        state: String,
+    },
+    GithubGetPullRequest {
+        pull_request_number: u64,
+    },
+    GithubGetPullRequestDiff {
+        pull_request_number: u64,
+    },
+    GithubGetPullRequestReviewComments {
+        pull_request_number: u64,
+    },
+    GithubCommentOnPullRequest {
+        pull_request_number: u64,
+        body: String,
+    },
     Sleep {
         duration: u64, // Duration in seconds
     },
@@ -178,7 +260,14 @@ impl Actions {
         match self {
             Actions::RunLLMInference { .. } => "run_llm_inference",
             Actions::ListAllFiles => "list_all_files",
+            Actions::ListFileTree => "list_file_tree",
             Actions::ReadASingleFile { .. } => "read_a_single_file",
+            Actions::OutlineFile { .. } => "outline_file",
+            Actions::SearchCode { .. } => "search_code",
+            Actions::GitLog { .. } => "git_log",
+            Actions::GitShow { .. } => "git_show",
+            Actions::GitDiff { .. } => "git_diff",
+            Actions::GitBlame { .. } => "git_blame",
             Actions::RunCommand { .. } => "run_command",
             
             Actions::StoreOrUpdateMemoryInContext { .. } => "store_or_update_memory_in_context",
@@ -192,6 +281,13 @@ impl Actions {
             Actions::GithubCommentOnIssue { .. } => "github_comment_on_issue",
             Actions::GithubEditBodyOfIssue { .. } => "github_edit_body_of_issue",
             Actions::GithubEditTitleOfIssue { .. } => "github_edit_title_of_issue",
+            Actions::GithubListPullRequests { .. } => "github_list_pull_requests",
+            Actions::GithubGetPullRequest { .. } => "github_get_pull_request",
+            Actions::GithubGetPullRequestDiff { .. } => "github_get_pull_request_diff",
+            Actions::GithubGetPullRequestReviewComments { .. } => {
+                "github_get_pull_request_review_comments"
+            }
+            Actions::GithubCommentOnPullRequest { .. } => "github_comment_on_pull_request",
 
             Actions::Sleep { .. } => "sleep",
             Actions::MarkComplete => "mark_complete",
@@ -205,12 +301,48 @@ impl Actions {
                 Returns the generated text from the LLM."
             }
             Actions::ListAllFiles => {
-                "List all files in the repository and returns a list of file paths. e.g. ['src/lib.rs', 'src/main.rs']"
+                "List all files in the repository and returns their paths relative to the repository root, one per line.\
+                Ignored, binary, vendored and generated files are left out. e.g. 'src/lib.rs'"
+            }
+            Actions::ListFileTree => {
+                "List the same files as list_all_files as an indented directory tree,\
+                with the size and line count of each file. Use this to plan which files to read."
             }
             Actions::ReadASingleFile { .. } => {
                 "Read a single file in the repository and returns the content of the file.\
                 The file is identified by its path, which is a string.\
-                Use this to read the content of a specific file in the repository."
+                Optionally pass start_line and end_line (1-based, inclusive) to read only part of it,\
+                and max_bytes to lower the output budget.\
+                Long files are truncated, the output then says which lines were returned.\
+                Use outline_file first to find the lines worth reading in a large file."
+            }
+            Actions::OutlineFile { .. } => {
+                "Get the outline of a file: its top-level items (functions, structs, impls, classes, headings)\
+                with their line numbers. Use this to navigate a file before reading parts of it."
+            }
+            Actions::SearchCode { .. } => {
+                "Search the repository's files for lines matching a regular expression (Rust regex syntax, prefix with (?i) to ignore case).\
+                Optionally pass a glob to restrict the files, e.g. \"*.rs\" or \"src/**/*.py\",\
+                context_lines (up to 5) to show lines around each match and max_results (default 50, at most 200).\
+                Files ignored by .gitignore, binaries and vendored code are not searched.\
+                Use this to find definitions, usages or TODO comments instead of reading files one by one."
+            }
+            Actions::GitLog { .. } => {
+                "List commits, newest first, one line each: short hash, date, author and summary.\
+                range is a revision (default HEAD) or a range like \"abc1234..HEAD\",\
+                path keeps only commits touching that file or directory, max_count defaults to 20."
+            }
+            Actions::GitShow { .. } => {
+                "Show a commit (hash, branch or tag): its author, date, message, changed files and diff.\
+                Set stat_only to true to get only the list of changed files with line counts."
+            }
+            Actions::GitDiff { .. } => {
+                "Show the diff between two revisions, e.g. from \"v1.0\" to \"HEAD\".\
+                Optionally pass a path to limit it to a file or directory, and stat_only to get only the summary."
+            }
+            Actions::GitBlame { .. } => {
+                "Show, for each line of a file at HEAD, the commit, author and date that last changed it.\
+                Pass start_line and end_line (1-based, inclusive) to blame only part of the file."
             }
             Actions::RunCommand { .. } => {
                 "Run a command in the shell and returns the output of the command.\
@@ -264,6 +396,29 @@ impl Actions {
                 "Edit the title of a specific issue in the GitHub repository.\
                 The issue is identified by its number, which is a u64, and the new title is a string."
             }
+            Actions::GithubListPullRequests { .. } => {
+                "List pull requests in the GitHub repository.\
+                The state is a string: 'open', 'closed', 'merged' or 'all'.\
+                Returns each pull request with its branches and the issues it links with 'Fixes #N'."
+            }
+            Actions::GithubGetPullRequest { .. } => {
+                "Get a specific pull request from the GitHub repository.\
+                The pull request is identified by its number, which is a u64.\
+                Returns its details, linked issues and the list of changed files."
+            }
+            Actions::GithubGetPullRequestDiff { .. } => {
+                "Get the unified diff of a specific pull request.\
+                The pull request is identified by its number, which is a u64.\
+                Use this to check whether a pull request really resolves an issue."
+            }
+            Actions::GithubGetPullRequestReviewComments { .. } => {
+                "Get the review comments left on the code of a specific pull request.\
+                The pull request is identified by its number, which is a u64."
+            }
+            Actions::GithubCommentOnPullRequest { .. } => {
+                "Add a comment to a specific pull request in the GitHub repository.\
+                The pull request is identified by its number, which is a u64, and the comment body is a string."
+            }
             Actions::Sleep { .. } => {
                 "Sleep for a specified duration in seconds.\
                 This action is used to pause the agent's execution for a while.
@@ -275,6 +430,224 @@ impl Actions {
             }
         }
     }
+
+    /// Whether the action writes to the forge. These are only recorded in dry-run mode.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Actions::GithubCreateIssue { .. }
+            | Actions::GithubAddLabelToIssue { .. }
+            | Actions::GithubRemoveLabelFromIssue { .. }
+            | Actions::GithubCloseIssue { .. }
+            | Actions::GithubCommentOnIssue { .. }
+            | Actions::GithubEditBodyOfIssue { .. }
+            | Actions::GithubEditTitleOfIssue { .. }
+            | Actions::GithubCommentOnPullRequest { .. } => true,
+
+            Actions::RunLLMInference { .. }
+            | Actions::ListAllFiles
+            | Actions::ListFileTree
+            | Actions::ReadASingleFile { .. }
+            | Actions::OutlineFile { .. }
+            | Actions::SearchCode { .. }
+            | Actions::GitLog { .. }
+            | Actions::GitShow { .. }
+            | Actions::GitDiff { .. }
+            | Actions::GitBlame { .. }
+            | Actions::RunCommand { .. }
+            | Actions::StoreOrUpdateMemoryInContext { .. }
+            | Actions::RemoveMemoryFromContext { .. }
+            | Actions::GithubGetIssue { .. }
+            | Actions::GithubListPullRequests { .. }
+            | Actions::GithubGetPullRequest { .. }
+            | Actions::GithubGetPullRequestDiff { .. }
+            | Actions::GithubGetPullRequestReviewComments { .. }
+            | Actions::Sleep { .. }
+            | Actions::MarkComplete => false,
+        }
+    }
+
+    /// JSON schema of the action's arguments, as expected by the `tools` API.
+    pub fn parameters(&self) -> Value {
+        match self {
+            Actions::RunLLMInference { .. } => object_schema(&[
+                ("system_prompt", string_schema()),
+                ("user_prompt", string_schema()),
+            ]),
+            Actions::ListAllFiles | Actions::ListFileTree => object_schema(&[]),
+            Actions::ReadASingleFile { .. } => object_schema_with_optional(
+                &[("path", string_schema())],
+                &[
+                    ("start_line", optional_number_schema(1)),
+                    ("end_line", optional_number_schema(1)),
+                    ("max_bytes", optional_number_schema(1)),
+                ],
+            ),
+            Actions::OutlineFile { .. } => object_schema(&[("path", string_schema())]),
+            Actions::SearchCode { .. } => object_schema_with_optional(
+                &[("pattern", string_schema())],
+                &[
+                    ("glob", optional_string_schema()),
+                    ("context_lines", optional_number_schema(0)),
+                    ("max_results", optional_number_schema(1)),
+                ],
+            ),
+            Actions::GitLog { .. } => object_schema_with_optional(
+                &[],
+                &[
+                    ("range", optional_string_schema()),
+                    ("path", optional_string_schema()),
+                    ("max_count", optional_number_schema(1)),
+                ],
+            ),
+            Actions::GitShow { .. } => object_schema_with_optional(
+                &[("revision", string_schema())],
+                &[("stat_only", optional_boolean_schema())],
+            ),
+            Actions::GitDiff { .. } => object_schema_with_optional(
+                &[("from", string_schema()), ("to", string_schema())],
+                &[
+                    ("path", optional_string_schema()),
+                    ("stat_only", optional_boolean_schema()),
+                ],
+            ),
+            Actions::GitBlame { .. } => object_schema_with_optional(
+                &[("path", string_schema())],
+                &[
+                    ("start_line", optional_number_schema(1)),
+                    ("end_line", optional_number_schema(1)),
+                ],
+            ),
+            Actions::RunCommand { .. } => object_schema(&[("command", string_schema())]),
+
+            Actions::StoreOrUpdateMemoryInContext { .. } => {
+                object_schema(&[("key", string_schema()), ("value", string_schema())])
+            }
+            Actions::RemoveMemoryFromContext { .. } => object_schema(&[("key", string_schema())]),
+
+            Actions::GithubCreateIssue { .. } => object_schema(&[
+                ("title", string_schema()),
+                ("body", string_schema()),
+                ("labels", json!({"type": "array", "items": string_schema()})),
+            ]),
+            Actions::GithubGetIssue { .. } | Actions::GithubCloseIssue { .. } => {
+                object_schema(&[("issue_number", number_schema())])
+            }
+            Actions::GithubAddLabelToIssue { .. } | Actions::GithubRemoveLabelFromIssue { .. } => {
+                object_schema(&[
+                    ("issue_number", number_schema()),
+                    ("label", string_schema()),
+                ])
+            }
+            Actions::GithubCommentOnIssue { .. } | Actions::GithubEditBodyOfIssue { .. } => {
+                object_schema(&[("issue_number", number_schema()), ("body", string_schema())])
+            }
+            Actions::GithubEditTitleOfIssue { .. } => object_schema(&[
+                ("issue_number", number_schema()),
+                ("title", string_schema()),
+            ]),
+            Actions::GithubListPullRequests { .. } => object_schema(&[(
+                "state",
+                json!({"type": "string", "enum": ["open", "closed", "merged", "all"]}),
+            )]),
+            Actions::GithubGetPullRequest { .. }
+            | Actions::GithubGetPullRequestDiff { .. }
+            | Actions::GithubGetPullRequestReviewComments { .. } => {
+                object_schema(&[("pull_request_number", number_schema())])
+            }
+            Actions::GithubCommentOnPullRequest { .. } => object_schema(&[
+                ("pull_request_number", number_schema()),
+                ("body", string_schema()),
+            ]),
+
+            Actions::Sleep { .. } => object_schema(&[(
+                "duration",
+                json!({"type": "integer", "minimum": 0, "description": "Duration in seconds"}),
+            )]),
+            Actions::MarkComplete => object_schema(&[]),
+        }
+    }
+
+    /// Whether the action takes no arguments and serializes as a bare string.
+    fn is_unit(&self) -> bool {
+        matches!(
+            self,
+            Actions::ListAllFiles | Actions::ListFileTree | Actions::MarkComplete
+        )
+    }
+
+    /// JSON schema of the action as it appears in the `===` delimited array:
+    /// a bare string for actions without arguments, `{"name": {..}}` otherwise.
+    pub fn schema(&self) -> Value {
+        if self.is_unit() {
+            json!({"const": self.name(), "description": self.desc()})
+        } else {
+            json!({
+                "type": "object",
+                "description": self.desc(),
+                "properties": { self.name(): self.parameters() },
+                "required": [self.name()],
+                "additionalProperties": false,
+            })
+        }
+    }
+
+    /// Builds an action from the name and arguments of a tool call, validating the
+    /// arguments against `parameters()` so errors name the offending field.
+    pub fn from_arguments(name: &str, arguments: Option<&Value>) -> Result<Self> {
+        let action = Actions::iter()
+            .find(|a| a.name() == name)
+            .ok_or_else(|| anyhow!("Unknown action `{}`", name))?;
+
+        let value = if action.is_unit() {
+            Value::String(name.to_string())
+        } else {
+            let arguments = arguments.cloned().unwrap_or_else(|| json!({}));
+            let errors = schema::validate(&action.parameters(), &arguments, name);
+            if !errors.is_empty() {
+                return Err(anyhow!("{}", errors.join("; ")));
+            }
+            json!({ name: arguments })
+        };
+        serde_json::from_value(value).context(format!("Invalid arguments for `{}`", name))
+    }
+
+    /// Builds an action from the name and raw JSON arguments of a tool call.
+    pub fn from_tool_call(name: &str, arguments: &str) -> Result<Self> {
+        let arguments: Option<Value> = if arguments.trim().is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::from_str(arguments)
+                    .context(format!("Invalid JSON arguments for `{}`", name))?,
+            )
+        };
+        Actions::from_arguments(name, arguments.as_ref())
+    }
+
+    /// Builds an action from one element of the `===` delimited array.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(name) => Actions::from_arguments(name, None),
+            Value::Object(object) if object.len() == 1 => {
+                let (name, arguments) = object.iter().next().unwrap();
+                Actions::from_arguments(name, Some(arguments))
+            }
+            _ => Err(anyhow!(
+                "expected an action name or an object with a single action key, got {}",
+                value
+            )),
+        }
+    }
+}
+
+/// JSON schema of the array of actions expected from the `===` text protocol.
+pub fn actions_schema() -> Value {
+    json!({
+        "type": "array",
+        "items": {
+            "oneOf": Actions::iter().map(|a| a.schema()).collect::<Vec<Value>>(),
+        },
+    })
 }
 
 pub fn general_system_prompt() -> String {
@@ -282,15 +655,88 @@ pub fn general_system_prompt() -> String {
     for action in Actions::iter() {
         let name = action.name();
         let desc = action.desc();
-        let json = serde_json::to_string(&action).unwrap();
-        p.push_str(&format!("- `{}`: {} (JSON: `{}`)\n", name, desc, json));
+        if action.is_unit() {
+            p.push_str(&format!("- `{}`: {} (no arguments)\n", name, desc));
+        } else {
+            p.push_str(&format!(
+                "- `{}`: {} (arguments schema: `{}`)\n",
+                name,
+                desc,
+                action.parameters()
+            ));
+        }
     }
-    p.push_str("\n");
+    p.push('\n');
     p
 }
 
 pub fn thinking_system_prompt() -> String {
-    format!("{}{}", general_system_prompt(), THINKING_ADD_ON)
+    format!(
+        "{}{}\nThe actions array must validate against this JSON schema:\n{}\n",
+        general_system_prompt(),
+        THINKING_ADD_ON,
+        actions_schema()
+    )
+}
+
+/// System prompt used when actions are sent as native tools instead of `===` delimited JSON.
+pub fn tool_calling_system_prompt() -> String {
+    format!("{}{}", general_system_prompt(), TOOL_CALLING_ADD_ON)
+}
+
+/// Every action as an OpenAI `tools` entry.
+pub fn tool_definitions() -> Vec<Value> {
+    Actions::iter()
+        .map(|action| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": action.name(),
+                    "description": action.desc(),
+                    "parameters": action.parameters(),
+                }
+            })
+        })
+        .collect()
+}
+
+fn string_schema() -> Value {
+    json!({"type": "string"})
+}
+
+fn number_schema() -> Value {
+    json!({"type": "integer", "minimum": 0})
+}
+
+fn optional_string_schema() -> Value {
+    json!({"type": ["string", "null"]})
+}
+
+fn optional_boolean_schema() -> Value {
+    json!({"type": ["boolean", "null"]})
+}
+
+fn optional_number_schema(minimum: u64) -> Value {
+    json!({"type": ["integer", "null"], "minimum": minimum})
+}
+
+fn object_schema(properties: &[(&str, Value)]) -> Value {
+    object_schema_with_optional(properties, &[])
+}
+
+fn object_schema_with_optional(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties: serde_json::Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let required: Vec<&str> = required.iter().map(|(name, _)| *name).collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
 }
 
 #[cfg(test)]
@@ -328,4 +774,81 @@ mod tests {
         assert!(prompt.contains(THINKING_ADD_ON));
         println!("Thinking System Prompt: {}", prompt);
     }
+
+    #[test]
+    fn test_parameters_match_serde_fields() {
+        for action in Actions::iter() {
+            let serialized = serde_json::to_value(&action).unwrap();
+            let mut fields: Vec<String> = match serialized.get(action.name()) {
+                Some(Value::Object(fields)) => fields.keys().cloned().collect(),
+                _ => vec![],
+            };
+            let mut properties: Vec<String> = action.parameters()["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            fields.sort();
+            properties.sort();
+            assert_eq!(fields, properties, "Schema mismatch for {}", action.name());
+        }
+    }
+
+    #[test]
+    fn test_from_tool_call() {
+        assert_eq!(
+            Actions::from_tool_call("github_close_issue", r#"{"issue_number": 3}"#).unwrap(),
+            Actions::GithubCloseIssue { issue_number: 3 }
+        );
+        assert_eq!(
+            Actions::from_tool_call("list_all_files", "{}").unwrap(),
+            Actions::ListAllFiles
+        );
+        // Code in arguments must survive untouched
+        assert_eq!(
+            Actions::from_tool_call(
+                "github_comment_on_issue",
+                r#"{"issue_number": 1, "body": "```rust\nfn main() {}\n```"}"#
+            )
+            .unwrap(),
+            Actions::GithubCommentOnIssue {
+                issue_number: 1,
+                body: "```rust\nfn main() {}\n```".to_string()
+            }
+        );
+        assert!(Actions::from_tool_call("unknown", "{}").is_err());
+        assert_eq!(
+            Actions::from_tool_call("github_close_issue", "{}")
+                .unwrap_err()
+                .to_string(),
+            "github_close_issue.issue_number: missing required field"
+        );
+    }
+
+    #[test]
+    fn test_actions_schema() {
+        let schema = actions_schema();
+        let variants = schema["items"]["oneOf"].as_array().unwrap();
+        assert_eq!(variants.len(), Actions::iter().count());
+
+        let action = serde_json::to_value(Actions::GithubCreateIssue {
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+            labels: vec!["bug".to_string()],
+        })
+        .unwrap();
+        let create_issue = Actions::iter()
+            .find(|a| a.name() == "github_create_issue")
+            .unwrap();
+        assert!(schema::validate(&create_issue.schema(), &action, "action").is_empty());
+        assert_eq!(
+            Actions::from_json(&action).unwrap().name(),
+            "github_create_issue"
+        );
+        assert_eq!(
+            Actions::from_json(&json!("mark_complete")).unwrap(),
+            Actions::MarkComplete
+        );
+    }
 }
@@ -1,49 +1,466 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
 
-use crate::actions::{Actions, thinking_system_prompt};
+use crate::actions::{
+    Actions, thinking_system_prompt, tool_calling_system_prompt, tool_definitions,
+};
+use crate::approval::{ActionPolicy, ApprovalQueue, ApprovalStatus};
 use crate::config;
-use crate::github;
+use crate::dry_run;
+use crate::forge::{self, Forge};
+use crate::issue_store::IssueStore;
 use crate::llm;
 use crate::monitoring::Monitor;
-use crate::repository;
+use crate::outline;
+use crate::repository::{self, MAX_COMMIT_EVENTS};
+use crate::sandbox;
+use crate::state;
+use crate::webhook::WebhookEvent;
+use tokio::sync::mpsc::UnboundedReceiver;
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum Event {
     NewCommit {
+        /// Empty in events persisted before several branches could be watched.
+        #[serde(default)]
+        branch: String,
         commit_hash: String,
+        author: String,
         message: String,
+        changed_files: Vec<String>,
+    },
+    /// Several commits at once, summarised in a single event.
+    NewCommits {
+        /// Empty in events persisted before several branches could be watched.
+        #[serde(default)]
+        branch: String,
+        from: String,
+        to: String,
+        /// Number of commits. Zero in events persisted before it was recorded.
+        #[serde(default)]
+        count: usize,
+        /// One line per commit, oldest first, abbreviated in the middle.
+        commits: Vec<String>,
+        changed_files: Vec<String>,
+    },
+    /// The branch no longer contains its previous tip, e.g. after a force-push.
+    HistoryRewritten {
+        /// Empty in events persisted before several branches could be watched.
+        #[serde(default)]
+        branch: String,
+        old_head: String,
+        new_head: String,
+        dropped_commits: Vec<String>,
+    },
+    NewTag {
+        name: String,
+        commit_hash: String,
+        message: String,
+    },
+    NewRelease {
+        tag_name: String,
+        name: String,
+        author: String,
+        body: String,
+        prerelease: bool,
+        linked_issues: Vec<u64>,
     },
     NewIssue {
         issue_number: u64,
         title: String,
+        author: String,
         body: String,
     },
+    IssueUpdated {
+        issue_number: u64,
+        title: String,
+    },
+    IssueTitleChanged {
+        issue_number: u64,
+        old_title: String,
+        new_title: String,
+    },
+    IssueBodyChanged {
+        issue_number: u64,
+        title: String,
+        body: String,
+    },
+    IssueLabelsChanged {
+        issue_number: u64,
+        title: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    IssueStateChanged {
+        issue_number: u64,
+        title: String,
+        state: String,
+    },
     NewComment {
         issue_number: u64,
+        /// Zero in events persisted before comments were tracked by ID.
+        #[serde(default)]
+        comment_id: u64,
+        author: String,
         body: String,
     },
     NewPullRequest {
         pull_request_number: u64,
         title: String,
+        author: String,
         body: String,
+        linked_issues: Vec<u64>,
+    },
+    PullRequestMerged {
+        pull_request_number: u64,
+        title: String,
+        linked_issues: Vec<u64>,
+    },
+    PullRequestClosed {
+        pull_request_number: u64,
+        title: String,
     },
 }
 
+impl std::fmt::Display for Event {
+    /// Formats the event for adding to the context prompt.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::NewCommit {
+                branch,
+                commit_hash,
+                author,
+                message,
+                changed_files,
+            } => write!(
+                f,
+                "New commit {} on {} by {}: {}\nChanged files: {}",
+                commit_hash,
+                branch,
+                author,
+                message.trim(),
+                changed_files.join(", ")
+            ),
+            Event::NewCommits {
+                branch,
+                from,
+                to,
+                count,
+                commits,
+                changed_files,
+            } => write!(
+                f,
+                "{} new commits on {} from {} to {}:\n{}\nChanged files: {}",
+                if *count == 0 { commits.len() } else { *count },
+                branch,
+                from,
+                to,
+                commits.join("\n"),
+                changed_files.join(", ")
+            ),
+            Event::HistoryRewritten {
+                branch,
+                old_head,
+                new_head,
+                dropped_commits,
+            } => {
+                write!(
+                    f,
+                    "History of {} rewritten (force-push): the branch moved from {} to {}, which does not contain it.",
+                    branch, old_head, new_head
+                )?;
+                if !dropped_commits.is_empty() {
+                    write!(
+                        f,
+                        "\nCommits no longer on the branch:\n{}",
+                        dropped_commits.join("\n")
+                    )?;
+                }
+                Ok(())
+            }
+            Event::NewTag {
+                name,
+                commit_hash,
+                message,
+            } => write!(
+                f,
+                "New tag {} on commit {}\n{}",
+                name,
+                commit_hash,
+                message.trim()
+            ),
+            Event::NewRelease {
+                tag_name,
+                name,
+                author,
+                body,
+                prerelease,
+                linked_issues,
+            } => write!(
+                f,
+                "New {} {} ({}) published by {} (linked issues: {:?})\n{}",
+                if *prerelease {
+                    "pre-release"
+                } else {
+                    "release"
+                },
+                name,
+                tag_name,
+                author,
+                linked_issues,
+                body
+            ),
+            Event::NewIssue {
+                issue_number,
+                title,
+                author,
+                body,
+            } => write!(
+                f,
+                "New issue #{} by {}: {}\n{}",
+                issue_number, author, title, body
+            ),
+            Event::IssueUpdated {
+                issue_number,
+                title,
+            } => write!(f, "Issue #{} updated: {}", issue_number, title),
+            Event::IssueTitleChanged {
+                issue_number,
+                old_title,
+                new_title,
+            } => write!(
+                f,
+                "Issue #{} renamed from \"{}\" to \"{}\"",
+                issue_number, old_title, new_title
+            ),
+            Event::IssueBodyChanged {
+                issue_number,
+                title,
+                body,
+            } => write!(
+                f,
+                "Body of issue #{} ({}) edited:\n{}",
+                issue_number, title, body
+            ),
+            Event::IssueLabelsChanged {
+                issue_number,
+                title,
+                added,
+                removed,
+            } => write!(
+                f,
+                "Labels of issue #{} ({}) changed: added {:?}, removed {:?}",
+                issue_number, title, added, removed
+            ),
+            Event::IssueStateChanged {
+                issue_number,
+                title,
+                state,
+            } => write!(f, "Issue #{} ({}) is now {}", issue_number, title, state),
+            Event::NewComment {
+                issue_number,
+                author,
+                body,
+                ..
+            } => write!(
+                f,
+                "New comment on issue #{} by {}: {}",
+                issue_number, author, body
+            ),
+            Event::NewPullRequest {
+                pull_request_number,
+                title,
+                author,
+                body,
+                linked_issues,
+            } => write!(
+                f,
+                "New pull request #{} by {}: {} (linked issues: {:?})\n{}",
+                pull_request_number, author, title, linked_issues, body
+            ),
+            Event::PullRequestMerged {
+                pull_request_number,
+                title,
+                linked_issues,
+            } => write!(
+                f,
+                "Pull request #{} merged: {} (linked issues: {:?})",
+                pull_request_number, title, linked_issues
+            ),
+            Event::PullRequestClosed {
+                pull_request_number,
+                title,
+            } => write!(
+                f,
+                "Pull request #{} closed without merging: {}",
+                pull_request_number, title
+            ),
+        }
+    }
+}
+
 const MAX_PAST_EVENTS: usize = 5;
 
+/// Commits listed at each end of a summary of many commits.
+const SUMMARY_COMMITS: usize = MAX_COMMIT_EVENTS / 2;
+
+/// Changed files listed in a summary of many commits.
+const SUMMARY_FILES: usize = 50;
+
+/// `- abc1234 Ada: summary`, for events listing several commits.
+fn commit_summary(commit: &repository::CommitInfo) -> String {
+    format!(
+        "- {} {}: {}",
+        &commit.hash[..7.min(commit.hash.len())],
+        commit.author,
+        commit.message.lines().next().unwrap_or("")
+    )
+}
+
+/// One line per commit, or only the first and last `SUMMARY_COMMITS` above
+/// `MAX_COMMIT_EVENTS` commits. `truncated` marks that older commits were not walked.
+fn commit_summaries(commits: &[repository::CommitInfo], truncated: bool) -> Vec<String> {
+    if commits.len() <= MAX_COMMIT_EVENTS {
+        return commits.iter().map(commit_summary).collect();
+    }
+    let mut lines = Vec::new();
+    if truncated {
+        lines.push("- ... older commits not listed".to_string());
+    }
+    lines.extend(commits[..SUMMARY_COMMITS].iter().map(commit_summary));
+    lines.push(format!(
+        "- ... {} more commits",
+        commits.len() - 2 * SUMMARY_COMMITS
+    ));
+    lines.extend(
+        commits[commits.len() - SUMMARY_COMMITS..]
+            .iter()
+            .map(commit_summary),
+    );
+    lines
+}
+
+/// Pause between two thinking iterations while the agent still has work to do.
+const THINK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Appended to every comment the agent posts, so it can recognise its own comments.
+const SIGNATURE: &str = "From: Pristine";
+
+/// Events for the commits that reached a branch: a rewrite first if any, then one
+/// event per commit, or a single summary above `MAX_COMMIT_EVENTS` commits.
+fn commit_events(change: repository::NewCommits) -> Vec<Event> {
+    let mut events = Vec::new();
+    let branch = change.branch;
+    if let Some(rewrite) = change.rewrite {
+        println!(
+            "History of {} rewritten: {} replaced by {}",
+            branch, rewrite.old_head, rewrite.new_head
+        );
+        events.push(Event::HistoryRewritten {
+            branch: branch.clone(),
+            old_head: rewrite.old_head,
+            new_head: rewrite.new_head,
+            dropped_commits: commit_summaries(&rewrite.dropped, rewrite.truncated),
+        });
+    }
+
+    let commits = change.commits;
+    if commits.len() > MAX_COMMIT_EVENTS {
+        println!("{} new commits detected on {}", commits.len(), branch);
+        let mut changed_files = change.changed_files;
+        if changed_files.len() > SUMMARY_FILES {
+            let more = changed_files.len() - SUMMARY_FILES;
+            changed_files.truncate(SUMMARY_FILES);
+            changed_files.push(format!("... and {} more", more));
+        }
+        events.push(Event::NewCommits {
+            branch,
+            from: commits[0].hash.clone(),
+            to: commits[commits.len() - 1].hash.clone(),
+            count: commits.len(),
+            commits: commit_summaries(&commits, change.truncated),
+            changed_files,
+        });
+        return events;
+    }
+    for commit in commits {
+        println!("New commit detected on {}: {}", branch, commit.hash);
+        events.push(Event::NewCommit {
+            branch: branch.clone(),
+            commit_hash: commit.hash,
+            author: commit.author,
+            message: commit.message,
+            changed_files: commit.changed_files,
+        });
+    }
+    events
+}
+
+fn is_own_comment(body: &str) -> bool {
+    body.trim_end().trim_end_matches('.').ends_with(SIGNATURE)
+}
+
+/// Compares two snapshots of the same issue and reports each field that changed.
+fn diff_issue(known: &forge::Issue, current: &forge::Issue) -> Vec<Event> {
+    let mut events = Vec::new();
+    if known.title != current.title {
+        events.push(Event::IssueTitleChanged {
+            issue_number: current.number,
+            old_title: known.title.clone(),
+            new_title: current.title.clone(),
+        });
+    }
+    if known.body != current.body {
+        events.push(Event::IssueBodyChanged {
+            issue_number: current.number,
+            title: current.title.clone(),
+            body: current.body.clone(),
+        });
+    }
+    let added: Vec<String> = current
+        .labels
+        .iter()
+        .filter(|label| !known.labels.contains(label))
+        .cloned()
+        .collect();
+    let removed: Vec<String> = known
+        .labels
+        .iter()
+        .filter(|label| !current.labels.contains(label))
+        .cloned()
+        .collect();
+    if !added.is_empty() || !removed.is_empty() {
+        events.push(Event::IssueLabelsChanged {
+            issue_number: current.number,
+            title: current.title.clone(),
+            added,
+            removed,
+        });
+    }
+    if known.state != current.state {
+        events.push(Event::IssueStateChanged {
+            issue_number: current.number,
+            title: current.title.clone(),
+            state: current.state.clone(),
+        });
+    }
+    events
+}
+
 pub struct AgentContext {
     memories: HashMap<String, String>,
 
     last_action_output: Option<String>,
     last_thought: Option<String>,
 
-    known_open_issues: Vec<github::Issue>,
+    known_open_issues: Vec<forge::Issue>,
     known_closed_issues_titles: Vec<String>,
-    past_events: Vec<String>,
-    new_event: Vec<String>,
+    known_open_pull_requests: Vec<forge::PullRequest>,
+    past_events: Vec<Event>,
+    new_event: Vec<Event>,
 
     error: Option<String>,
     is_complete: bool,
@@ -74,6 +491,18 @@ impl AgentContext {
             }
         }
 
+        if !self.known_open_pull_requests.is_empty() {
+            prompt.push_str("\nKnown Open Pull Requests:\n");
+            for pr in &self.known_open_pull_requests {
+                let duration = current_time.signed_duration_since(pr.updated_at);
+                let time_ago = format_duration(duration);
+                prompt.push_str(&format!(
+                    "Pull request #{}: {} (Updated {} ago)\nAuthor: {}\nLinked issues: {:?}\n",
+                    pr.number, pr.title, time_ago, pr.author, pr.linked_issues,
+                ));
+            }
+        }
+
         let past_events_to_display = self
             .past_events
             .iter()
@@ -105,7 +534,7 @@ impl AgentContext {
             prompt.push_str(&format!("\nError: {}\n", error));
         }
         self.error = None; // Clear error after displaying
-        prompt.push_str("\n");
+        prompt.push('\n');
 
         let current_size = prompt.len();
         prompt.push_str(&format!(
@@ -117,6 +546,39 @@ impl AgentContext {
     }
 }
 
+fn open_pull_requests(pull_requests: &HashMap<u64, forge::PullRequest>) -> Vec<forge::PullRequest> {
+    let mut open: Vec<forge::PullRequest> = pull_requests
+        .values()
+        .filter(|pr| pr.state == "open")
+        .cloned()
+        .collect();
+    open.sort_by_key(|pr| pr.number);
+    open
+}
+
+/// Open issues and closed issue titles for the prompt, by issue number.
+fn issue_context(issues: &IssueStore) -> (Vec<forge::Issue>, Vec<String>) {
+    let open = issues
+        .issues()
+        .filter(|issue| issue.state == "open")
+        .cloned()
+        .collect();
+    let closed_titles = issues
+        .issues()
+        .filter(|issue| issue.state == "closed")
+        .map(|issue| issue.title.clone())
+        .collect();
+    (open, closed_titles)
+}
+
+/// (author, body) pairs, as stored in `forge::Issue::comments`.
+fn comment_pairs(comments: &[forge::Comment]) -> Vec<(String, String)> {
+    comments
+        .iter()
+        .map(|c| (c.author.clone(), c.body.clone()))
+        .collect()
+}
+
 fn format_duration(duration: chrono::Duration) -> String {
     if duration.num_days() > 0 {
         format!("{} days", duration.num_days())
@@ -129,51 +591,174 @@ fn format_duration(duration: chrono::Duration) -> String {
     }
 }
 
+/// Extracts the JSON array of actions found between `===` markers in a thought.
+/// A code fence wrapping the array is tolerated, fences inside the JSON strings are kept.
+fn parse_text_actions(thought: &str) -> Result<Vec<Actions>, String> {
+    let Some(start) = thought.find("===") else {
+        return Err("No actions found in your output. Did you use equal signs: === ?".to_string());
+    };
+    let rest = &thought[start + 3..];
+    let actions = match rest.find("===") {
+        Some(end) => &rest[..end],
+        None => rest,
+    };
+    let actions = actions.trim();
+    let actions = actions
+        .strip_prefix("```json")
+        .or_else(|| actions.strip_prefix("```"))
+        .map(|a| a.trim_end().trim_end_matches("```"))
+        .unwrap_or(actions)
+        .trim();
+    if actions.is_empty() {
+        return Err("No actions found in the thought.".to_string());
+    }
+    let actions: Vec<serde_json::Value> = serde_json::from_str(actions)
+        .map_err(|e| format!("Failed to parse actions as a JSON array: {}", e))?;
+    collect_actions(
+        actions
+            .iter()
+            .enumerate()
+            .map(|(i, action)| (format!("actions[{}]", i), Actions::from_json(action))),
+    )
+}
+
+/// Keeps the parsed actions if all are valid, otherwise lists every invalid one.
+fn collect_actions(
+    results: impl Iterator<Item = (String, anyhow::Result<Actions>)>,
+) -> Result<Vec<Actions>, String> {
+    let mut actions = Vec::new();
+    let mut errors = Vec::new();
+    for (location, result) in results {
+        match result {
+            Ok(action) => actions.push(action),
+            Err(e) => errors.push(format!("- {}: {:#}", location, e)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(actions)
+    } else {
+        Err(format!(
+            "Invalid actions, none were executed:\n{}",
+            errors.join("\n")
+        ))
+    }
+}
+
 pub struct Agent {
-    github: github::GitHubClient,
+    forge: Box<dyn Forge>,
     repo: repository::RepositoryManager,
     llm: llm::LlmClient,
     monitor: Arc<Monitor>,
-    known_issues: Vec<github::Issue>,
+    state_store: state::StateStore,
+    issues: IssueStore,
+    known_pull_requests: HashMap<u64, forge::PullRequest>,
+    /// IDs of the releases seen so far, `None` until the first successful check.
+    known_releases: Option<HashSet<u64>>,
+    poll_interval: Duration,
+    webhook_events: Option<UnboundedReceiver<WebhookEvent>>,
+    pending_webhook_events: Vec<WebhookEvent>,
+    command_policy: sandbox::CommandPolicy,
+    approvals: ApprovalQueue,
+    action_policies: HashMap<String, ActionPolicy>,
+    /// Set in dry-run mode, receives every action that would write to GitHub.
+    dry_run: Option<dry_run::DryRunRecorder>,
+    /// Send actions as native tools instead of asking for `===` delimited JSON.
+    tool_calling: bool,
 
     agent_context: AgentContext,
 }
 
 impl Agent {
     pub async fn new(config: &config::Config) -> anyhow::Result<Self> {
-        let github = github::GitHubClient::new(config).await?;
-        let (repo_dir, repo) = github.clone_repository().await?;
-        let repo = repository::RepositoryManager::new(repo_dir, repo, config)?;
+        repository::allow_partial_clones();
+        let mut forge = forge::connect(config).await?;
+        let (workspace, repo) = forge.clone_repository(config).await?;
+        let repo = repository::RepositoryManager::new(workspace, repo, config)?;
         let mut llm = llm::LlmClient::new(config)?;
         let monitor = Arc::new(Monitor::new());
         llm.set_monitor(monitor.clone());
-        let known_issues = github.list_all_issues(None).await?;
-        let known_closed_issues_titles = known_issues
-            .iter()
-            .filter(|issue| issue.state == "closed")
-            .map(|issue| issue.title.clone())
-            .collect::<Vec<String>>();
-        let known_open_issues = known_issues
-            .iter()
-            .filter(|issue| issue.state == "open")
-            .cloned()
-            .collect::<Vec<github::Issue>>();
+        forge.set_monitor(monitor.clone());
+        let mut issues = IssueStore::open(&config.data_dir)?;
+        println!(
+            "Loaded {} cached issues from {}",
+            issues.len(),
+            issues.path().display()
+        );
+        // Changes made while the agent was stopped are applied without events
+        let issue_updates = forge
+            .list_issues_updated_since(issues.last_sync(), issues.etag())
+            .await?;
+        issues.set_sync(issue_updates.last_updated, issue_updates.etag);
+        for issue in issue_updates.issues.unwrap_or_default() {
+            issues.insert(issue);
+        }
+        if let Err(e) = issues.save() {
+            println!("Failed to save the issue cache: {}", e);
+        }
+        let (known_open_issues, known_closed_issues_titles) = issue_context(&issues);
+        let known_pull_requests = forge
+            .list_pull_requests(None)
+            .await?
+            .into_iter()
+            .map(|pr| (pr.number, pr))
+            .collect::<HashMap<u64, forge::PullRequest>>();
+        let known_open_pull_requests = open_pull_requests(&known_pull_requests);
+        let known_releases = match forge.list_recent_releases().await {
+            Ok(releases) => Some(releases.iter().map(|release| release.id).collect()),
+            Err(e) => {
+                println!("Failed to list releases: {}", e);
+                None
+            }
+        };
+
+        let dry_run = config.dry_run.then(|| {
+            // Issues and pull requests share numbers
+            let highest = issues
+                .highest_number()
+                .into_iter()
+                .chain(known_pull_requests.keys().copied())
+                .max()
+                .unwrap_or(0);
+            println!("Dry run: forge writes will only be recorded");
+            dry_run::DryRunRecorder::new(highest + 1, monitor.clone())
+        });
+
+        let state_store = state::StateStore::new(&config.data_dir)?;
+        let persisted = state_store.load()?;
+        println!(
+            "Loaded {} memories and {} past events from {}",
+            persisted.memories.len(),
+            persisted.past_events.len(),
+            state_store.path().display()
+        );
 
         Ok(Self {
-            github,
+            forge,
             repo,
             llm,
             monitor,
-            known_issues,
+            state_store,
+            issues,
+            known_pull_requests,
+            known_releases,
+            poll_interval: Duration::from_secs(config.poll_interval_secs),
+            webhook_events: None,
+            pending_webhook_events: Vec::new(),
+            command_policy: sandbox::CommandPolicy::from_config(config)?,
+            dry_run,
+            approvals: ApprovalQueue::open(&config.data_dir)?,
+            action_policies: config.action_policies.clone(),
+            tool_calling: config.openai_tool_calling,
             agent_context: AgentContext {
-                memories: HashMap::new(),
+                memories: persisted.memories,
                 known_open_issues,
                 known_closed_issues_titles,
-                past_events: Vec::new(),
+                known_open_pull_requests,
+                past_events: persisted.past_events,
                 new_event: Vec::new(),
 
                 last_action_output: None,
-                last_thought: None,
+                last_thought: persisted.last_thought,
                 error: None,
                 is_complete: false,
             },
@@ -196,92 +781,397 @@ impl Agent {
         self.monitor.clone()
     }
 
-    pub async fn check_for_events(&mut self) -> Vec<String> {
-        let mut events = vec![];
+    /// Queue of actions waiting for a maintainer, served by the web server.
+    pub fn get_approval_queue(&self) -> ApprovalQueue {
+        self.approvals.clone()
+    }
 
-        // Check for new commits
-        if self.repo.pull().is_err() {
-            println!("Failed to pull the latest changes from the repository.");
-            return events;
+    /// Feeds webhook deliveries into the agent loop, in addition to polling.
+    pub fn set_webhook_receiver(&mut self, receiver: UnboundedReceiver<WebhookEvent>) {
+        self.webhook_events = Some(receiver);
+    }
+
+    /// Writes memories, past events and the last thought to the state store,
+    /// and the issue cache when it changed.
+    fn persist_state(&mut self) {
+        let state = state::PersistedState {
+            memories: self.agent_context.memories.clone(),
+            past_events: self.agent_context.past_events.clone(),
+            last_thought: self.agent_context.last_thought.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = self.state_store.save(&state) {
+            println!("Failed to persist agent state: {}", e);
         }
-        if self.repo.new_commit() {
-            let Ok(commit) = self.repo.get_latest_commit() else {
-                println!("Failed to get the latest commit.");
-                return events;
-            };
-            println!("New commit detected: {}", commit.id());
-            let event = format!(
-                "New commit detected: {} - {}",
-                commit.id(),
-                commit.message().unwrap_or("No message")
-            );
-            events.push(event);
+        if let Err(e) = self.issues.save() {
+            println!("Failed to save the issue cache: {}", e);
         }
+    }
 
-        // Get all current issues
-        let current_issues = match self.github.list_all_issues(None).await {
-            Ok(issues) => issues,
+    pub async fn check_for_events(&mut self) -> Vec<Event> {
+        let mut events = self.check_for_commits().await;
+        events.extend(self.check_releases().await);
+        events.extend(self.check_pull_requests().await);
+
+        let updates = match self
+            .forge
+            .list_issues_updated_since(self.issues.last_sync(), self.issues.etag())
+            .await
+        {
+            Ok(updates) => updates,
             Err(e) => {
                 println!("Failed to list issues: {}", e);
                 return events;
             }
         };
+        self.issues.set_sync(updates.last_updated, updates.etag);
+        let Some(updated_issues) = updates.issues else {
+            return events;
+        };
 
-        let known_issues_map: HashMap<u64, github::Issue> = self
-            .known_issues
-            .drain(..)
-            .map(|issue| (issue.number, issue))
-            .collect();
+        for issue in updated_issues {
+            let known_issue = self.issues.get(issue.number).cloned();
+            events.extend(self.issue_events(known_issue.as_ref(), &issue).await);
+            self.issues.insert(issue);
+        }
+        self.refresh_issue_context();
 
-        let mut new_known_issues = Vec::new();
-        for issue in current_issues {
-            if let Some(known_issue) = known_issues_map.get(&issue.number) {
-                // Issue exists, check for updates
-                if known_issue.updated_at != issue.updated_at
-                    || known_issue.comments_count != issue.comments_count
-                {
-                    events.push(format!("Issue #{} updated: {}", issue.number, issue.title));
+        events
+    }
+
+    /// The configured poll interval, stretched while the GitHub quota runs low.
+    fn current_poll_interval(&self) -> Duration {
+        let Some(rate_limit) = self.forge.rate_limit() else {
+            return self.poll_interval;
+        };
+        let interval = rate_limit.poll_interval(self.poll_interval, Utc::now());
+        if interval > self.poll_interval {
+            println!(
+                "GitHub quota low ({} of {} requests left until {}), polling every {} seconds",
+                rate_limit.remaining,
+                rate_limit.limit,
+                rate_limit.reset.to_rfc3339(),
+                interval.as_secs()
+            );
+        }
+        interval
+    }
+
+    async fn check_for_commits(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        // Installation tokens expire, so the remote gets a fresh one first
+        let url = self.forge.clone_url().await;
+        if let Err(e) = url.and_then(|url| self.repo.set_remote_url(&url)) {
+            println!("Failed to refresh the repository credentials: {}", e);
+        }
+        if let Err(e) = self.repo.pull() {
+            println!(
+                "Failed to pull the latest changes from the repository: {}",
+                e
+            );
+            return events;
+        }
+        match self.repo.new_commits() {
+            Ok(changes) => events.extend(changes.into_iter().flat_map(commit_events)),
+            Err(e) => println!("Failed to list new commits: {}", e),
+        }
+        match self.repo.new_tags() {
+            Ok(tags) => {
+                for tag in tags {
+                    println!("New tag detected: {}", tag.name);
+                    events.push(Event::NewTag {
+                        name: tag.name,
+                        commit_hash: tag.commit_hash,
+                        message: tag.message,
+                    });
                 }
-            } else {
-                // New issue
-                events.push(format!("New issue: #{} - {}", issue.number, issue.title));
             }
-            new_known_issues.push(issue);
+            Err(e) => println!("Failed to list tags: {}", e),
         }
-        self.known_issues = new_known_issues;
+        events
+    }
 
-        self.agent_context.known_open_issues = self
-            .known_issues
-            .iter()
-            .filter(|issue| issue.state == "open")
-            .cloned()
-            .collect();
-        self.agent_context.known_closed_issues_titles = self
-            .known_issues
-            .iter()
-            .filter(|issue| issue.state == "closed")
-            .map(|issue| issue.title.clone())
-            .collect();
+    /// Reports releases published since the last check.
+    async fn check_releases(&mut self) -> Vec<Event> {
+        let releases = match self.forge.list_recent_releases().await {
+            Ok(releases) => releases,
+            Err(e) => {
+                println!("Failed to list releases: {}", e);
+                return vec![];
+            }
+        };
+        let first_check = self.known_releases.is_none();
+        let known = self.known_releases.get_or_insert_with(HashSet::new);
+        let mut events = Vec::new();
+        for release in releases {
+            // The first successful check only records the existing releases
+            if known.insert(release.id) && !first_check {
+                println!("New release detected: {}", release.tag_name);
+                events.push(Event::NewRelease {
+                    tag_name: release.tag_name,
+                    name: release.name,
+                    author: release.author,
+                    body: release.body,
+                    prerelease: release.prerelease,
+                    linked_issues: release.linked_issues,
+                });
+            }
+        }
+        events
+    }
+
+    /// Compares a fresh snapshot of an issue with the known one and reports what changed.
+    async fn issue_events(
+        &mut self,
+        known_issue: Option<&forge::Issue>,
+        issue: &forge::Issue,
+    ) -> Vec<Event> {
+        let Some(known_issue) = known_issue else {
+            return vec![Event::NewIssue {
+                issue_number: issue.number,
+                title: issue.title.clone(),
+                author: issue.author.clone(),
+                body: issue.body.clone(),
+            }];
+        };
+
+        let mut events = diff_issue(known_issue, issue);
+        // The count alone misses a comment deleted and another posted in the
+        // same interval, so any update is checked against the high-water mark
+        if issue.comments_count > 0
+            && (issue.comments_count != known_issue.comments_count
+                || issue.updated_at != known_issue.updated_at)
+        {
+            events.extend(
+                self.new_comments(
+                    issue.number,
+                    issue
+                        .comments_count
+                        .saturating_sub(known_issue.comments_count),
+                )
+                .await,
+            );
+        }
+        if events.is_empty() && known_issue.updated_at != issue.updated_at {
+            // Something we do not track changed, e.g. assignees or milestone
+            events.push(Event::IssueUpdated {
+                issue_number: issue.number,
+                title: issue.title.clone(),
+            });
+        }
+        events
+    }
+
+    fn refresh_issue_context(&mut self) {
+        (
+            self.agent_context.known_open_issues,
+            self.agent_context.known_closed_issues_titles,
+        ) = issue_context(&self.issues);
+    }
+
+    /// Reports pull requests that were opened, merged or closed since the last check.
+    async fn check_pull_requests(&mut self) -> Vec<Event> {
+        let recent = match self.forge.list_recent_pull_requests().await {
+            Ok(prs) => prs,
+            Err(e) => {
+                println!("Failed to list pull requests: {}", e);
+                return vec![];
+            }
+        };
+
+        let mut events = Vec::new();
+        for pr in recent {
+            events.extend(self.track_pull_request(pr));
+        }
+        events
+    }
+
+    fn track_pull_request(&mut self, pr: forge::PullRequest) -> Vec<Event> {
+        let mut events = Vec::new();
+        let previous_state = self
+            .known_pull_requests
+            .get(&pr.number)
+            .map(|known| known.state.clone());
+        if previous_state.is_none() {
+            events.push(Event::NewPullRequest {
+                pull_request_number: pr.number,
+                title: pr.title.clone(),
+                author: pr.author.clone(),
+                body: pr.body.clone(),
+                linked_issues: pr.linked_issues.clone(),
+            });
+        }
+        if previous_state.as_deref() != Some(pr.state.as_str()) {
+            match pr.state.as_str() {
+                "merged" => events.push(Event::PullRequestMerged {
+                    pull_request_number: pr.number,
+                    title: pr.title.clone(),
+                    linked_issues: pr.linked_issues.clone(),
+                }),
+                "closed" => events.push(Event::PullRequestClosed {
+                    pull_request_number: pr.number,
+                    title: pr.title.clone(),
+                }),
+                _ => {}
+            }
+        }
+        self.known_pull_requests.insert(pr.number, pr);
+        self.agent_context.known_open_pull_requests = open_pull_requests(&self.known_pull_requests);
+        events
+    }
+
+    /// Turns webhook deliveries received since the last iteration into events.
+    /// Deliveries update the same state polling compares against, so the next poll
+    /// does not report them a second time.
+    async fn handle_webhook_events(&mut self) -> Vec<Event> {
+        let mut deliveries = std::mem::take(&mut self.pending_webhook_events);
+        if let Some(receiver) = &mut self.webhook_events {
+            while let Ok(delivery) = receiver.try_recv() {
+                deliveries.push(delivery);
+            }
+        }
+
+        let mut events = Vec::new();
+        for delivery in deliveries {
+            match delivery {
+                WebhookEvent::Issue(issue) => {
+                    let known_issue = self.issues.get(issue.number).cloned();
+                    events.extend(self.issue_events(known_issue.as_ref(), &issue).await);
+                    self.issues.insert(issue);
+                    self.refresh_issue_context();
+                }
+                WebhookEvent::IssueComment {
+                    issue_number,
+                    comment_id,
+                    author,
+                    body,
+                } => {
+                    if self
+                        .issues
+                        .comment_high_water(issue_number)
+                        .is_some_and(|mark| comment_id <= mark)
+                    {
+                        continue;
+                    }
+                    self.issues.set_comment_high_water(issue_number, comment_id);
+                    self.issues
+                        .add_comment(issue_number, author.clone(), body.clone());
+                    if !is_own_comment(&body) {
+                        events.push(Event::NewComment {
+                            issue_number,
+                            comment_id,
+                            author,
+                            body,
+                        });
+                    }
+                }
+                WebhookEvent::Push { branch, .. } => {
+                    if self.repo.is_watched(&branch) {
+                        events.extend(self.check_for_commits().await);
+                    }
+                }
+                WebhookEvent::TagPush { .. } => events.extend(self.check_for_commits().await),
+                WebhookEvent::Release => events.extend(self.check_releases().await),
+                WebhookEvent::PullRequest(pr) => events.extend(self.track_pull_request(pr)),
+            }
+        }
+        events
+    }
+
+    /// Sleeps until the next iteration is due, waking up early when a webhook delivery arrives.
+    async fn wait_for_next_iteration(&mut self, next_poll: Instant) {
+        let delay = if self.agent_context.is_complete {
+            next_poll.saturating_duration_since(Instant::now())
+        } else {
+            THINK_INTERVAL
+        };
+        let approvals = self.approvals.clone();
+        match &mut self.webhook_events {
+            Some(receiver) => tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = approvals.wait_for_decision() => {}
+                Some(delivery) = receiver.recv() => self.pending_webhook_events.push(delivery),
+            },
+            None => tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = approvals.wait_for_decision() => {}
+            },
+        }
+    }
+
+    /// Fetches the comments of an issue that are newer than its high-water mark.
+    /// Without a mark yet, the last `added` comments are treated as new.
+    /// Comments posted by the agent itself are skipped.
+    async fn new_comments(&mut self, issue_number: u64, added: usize) -> Vec<Event> {
+        let comments = match self.forge.get_issue_comments(issue_number).await {
+            Ok(comments) => comments,
+            Err(e) => {
+                println!(
+                    "Failed to fetch comments for issue #{}: {}",
+                    issue_number, e
+                );
+                return vec![];
+            }
+        };
 
+        let high_water = self.issues.comment_high_water(issue_number);
+        let skip = match high_water {
+            Some(_) => 0,
+            None => comments.len().saturating_sub(added),
+        };
+        let mut events = Vec::new();
+        for comment in comments.iter().skip(skip) {
+            if high_water.is_some_and(|mark| comment.id <= mark) {
+                continue;
+            }
+            if is_own_comment(&comment.body) {
+                continue;
+            }
+            events.push(Event::NewComment {
+                issue_number,
+                comment_id: comment.id,
+                author: comment.author.clone(),
+                body: comment.body.clone(),
+            });
+        }
+
+        if let Some(max_id) = comments.iter().map(|c| c.id).max() {
+            self.issues.set_comment_high_water(issue_number, max_id);
+        }
+        self.issues
+            .set_comments(issue_number, comment_pairs(&comments));
         events
     }
 
     pub async fn start(mut self) -> ! {
         println!("Starting agent...");
         println!("System prompt: \n{}", thinking_system_prompt());
+        let mut last_poll: Option<Instant> = None;
         loop {
             self.agent_context
                 .past_events
-                .extend(self.agent_context.new_event.drain(..));
+                .append(&mut self.agent_context.new_event);
             // Trim past_events to MAX_PAST_EVENTS
             if self.agent_context.past_events.len() > MAX_PAST_EVENTS {
                 self.agent_context
                     .past_events
                     .drain(0..self.agent_context.past_events.len() - MAX_PAST_EVENTS);
             }
-            let new_events = self.check_for_events().await;
+            let mut new_events = Vec::new();
+            let poll_interval = self.current_poll_interval();
+            if last_poll.is_none_or(|t| t.elapsed() >= poll_interval) {
+                new_events.extend(self.check_for_events().await);
+                last_poll = Some(Instant::now());
+            }
+            new_events.extend(self.handle_webhook_events().await);
+            if self.handle_approvals().await {
+                self.agent_context.is_complete = false;
+            }
             if !new_events.is_empty() {
                 println!("New events detected: {:?}", new_events);
+                for event in &new_events {
+                    self.monitor.log_event(event.clone());
+                }
                 self.agent_context.is_complete = false; // Reset completion status on new events
             }
             self.agent_context.new_event = new_events;
@@ -304,13 +1194,15 @@ impl Agent {
                     // Update the last action and output in the agent context
                     self.agent_context.last_action_output = Some(outputs.clone());
                 }
+                self.persist_state();
             } else {
                 println!(
                     "Agent is marked complete. Skipping inference and waiting for external event."
                 );
             }
             // Sleep for a while before the next iteration
-            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            let next_poll = last_poll.map_or_else(Instant::now, |t| t + poll_interval);
+            self.wait_for_next_iteration(next_poll).await;
         }
     }
 
@@ -323,107 +1215,300 @@ impl Agent {
         let mut prompt = self.agent_context.build_contextual_prompt();
         prompt.push_str("\n\nNow, think about what actions to take next.\n");
 
-        let thought = match self
+        let (thought, actions) = if self.tool_calling {
+            self.think_with_tools(&prompt).await
+        } else {
+            self.think_with_text(&prompt).await
+        };
+        let Some(thought) = thought else {
+            return vec![];
+        };
+        println!("Thought: {}", thought);
+
+        let actions = match actions {
+            Ok(a) => a,
+            Err(e) => {
+                println!("{}", e);
+                self.agent_context.error = Some(e);
+                return vec![];
+            }
+        };
+
+        self.agent_context.last_thought = Some(thought);
+
+        actions
+    }
+
+    async fn think_with_text(
+        &mut self,
+        prompt: &str,
+    ) -> (Option<String>, Result<Vec<Actions>, String>) {
+        match self
             .llm
-            .generate_text(&thinking_system_prompt(), &prompt)
+            .generate_text(&thinking_system_prompt(), prompt)
             .await
         {
-            Ok(t) => t,
+            Ok(thought) => {
+                let actions = parse_text_actions(&thought);
+                (Some(thought), actions)
+            }
             Err(e) => {
                 println!("Failed to generate thought: {}", e);
                 self.agent_context.error = Some(format!("Failed to generate thought: {}", e));
-                return vec![];
+                (None, Ok(vec![]))
+            }
+        }
+    }
+
+    async fn think_with_tools(
+        &mut self,
+        prompt: &str,
+    ) -> (Option<String>, Result<Vec<Actions>, String>) {
+        let response = match self
+            .llm
+            .generate_with_tools(&tool_calling_system_prompt(), prompt, &tool_definitions())
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Failed to generate thought: {}", e);
+                self.agent_context.error = Some(format!("Failed to generate thought: {}", e));
+                return (None, Ok(vec![]));
             }
         };
-        println!("Thought: {}", thought);
 
-        // split on json
-        let actions_sart = thought.find("===");
-        if actions_sart.is_none() {
-            println!("No actions found in the thought.");
-            self.agent_context.error =
-                Some("No actions found in your output. Did you use equal signs: === ?".to_string());
-            return vec![];
-        }
-        let actions = &thought[actions_sart.unwrap()..];
-        if actions.is_empty() {
-            println!("No actions found in the thought.");
-            self.agent_context.error = Some("No actions found in the thought.".to_string());
-            return vec![];
+        if response.tool_calls.is_empty() {
+            return (
+                Some(response.content),
+                Err("No tool calls found in your output. Call at least one tool.".to_string()),
+            );
         }
-        let actions = &actions.replace("===", "");
-        let actions = actions.trim();
-        let actions: Vec<Actions> = match serde_json::from_str(actions) {
-            Ok(a) => a,
-            Err(e) => {
-                println!("Failed to parse actions: {}", e);
-                self.agent_context.error = Some(format!("Failed to parse actions: {}", e));
-                return vec![];
+        let actions = collect_actions(response.tool_calls.iter().map(|call| {
+            (
+                format!("tool call `{}`", call.function.name),
+                Actions::from_tool_call(&call.function.name, &call.function.arguments),
+            )
+        }));
+        (Some(response.content), actions)
+    }
+
+    /// Applies the configured policy, then runs the action, parks it for approval or refuses it.
+    pub async fn act(&mut self, action: Actions) -> String {
+        let policy = self
+            .action_policies
+            .get(action.name())
+            .copied()
+            .unwrap_or(ActionPolicy::Auto);
+        let output = match policy {
+            ActionPolicy::Auto => return self.execute(action).await,
+            ActionPolicy::RequireApproval => {
+                let id = self.approvals.submit(action.clone());
+                format!(
+                    "Action {} is waiting for human approval as request #{}. It will run once a maintainer approves it, do not retry it.",
+                    action.name(),
+                    id
+                )
             }
+            ActionPolicy::Deny => format!(
+                "Action {} is denied by policy and was not executed.",
+                action.name()
+            ),
         };
+        println!("{}", output);
+        self.monitor.log_action(action, output.clone(), 0);
+        output
+    }
 
-        self.agent_context.last_thought = Some(thought);
+    /// Runs approved actions and reports rejected ones to the model.
+    /// Returns whether a maintainer decided anything since the last call.
+    async fn handle_approvals(&mut self) -> bool {
+        let decided = self.approvals.decided();
+        if decided.is_empty() {
+            return false;
+        }
 
-        actions
+        let mut outputs = String::new();
+        let mut rejections = Vec::new();
+        for item in decided {
+            match item.status {
+                ApprovalStatus::Approved => {
+                    let output = self.execute(item.action.clone()).await;
+                    self.approvals.mark_executed(item.id, output.clone());
+                    outputs.push_str(&format!(
+                        "Approved action (request #{}): {:?}\nOutput: {}\n",
+                        item.id, item.action, output
+                    ));
+                }
+                ApprovalStatus::Rejected { reason } => {
+                    rejections.push(format!(
+                        "A maintainer rejected action {:?} (request #{}): {}",
+                        item.action, item.id, reason
+                    ));
+                    self.approvals.mark_handled(item.id);
+                }
+                ApprovalStatus::Pending | ApprovalStatus::Executed { .. } => {}
+            }
+        }
+
+        if !outputs.is_empty() {
+            self.agent_context.last_action_output = Some(outputs);
+        }
+        if !rejections.is_empty() {
+            if let Some(error) = self.agent_context.error.take() {
+                rejections.insert(0, error);
+            }
+            self.agent_context.error = Some(rejections.join("\n"));
+        }
+        true
     }
 
-    pub async fn act(&mut self, action: Actions) -> String {
+    /// Surfaces refused paths as an error in the next prompt, not only as action output.
+    fn report_path_error(&mut self, error: &anyhow::Error) {
+        if let Some(path_error) = error.downcast_ref::<repository::PathError>() {
+            let message = format!("Path rejected: {}", path_error);
+            self.agent_context.error = Some(match self.agent_context.error.take() {
+                Some(previous) => format!("{}\n{}", previous, message),
+                None => message,
+            });
+        }
+    }
+
+    async fn execute(&mut self, action: Actions) -> String {
         println!("Acting on action: {:?}", action);
         let start_time = std::time::Instant::now();
         let action_clone = action.clone();
 
+        if action.is_mutating()
+            && let Some(recorder) = &mut self.dry_run
+        {
+            let output = recorder.record(&action);
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+            self.monitor.log_action(action, output.clone(), duration_ms);
+            return output;
+        }
+
         let output: String = match action {
             Actions::ListAllFiles => match self.repo.list_all_files().await {
-                Ok(files) => files.join(", "),
+                Ok(files) => files.join("\n"),
                 Err(e) => {
                     println!("Failed to list all files: {}", e);
                     format!("Failed to list all files: {}", e)
                 }
             },
-            Actions::ReadASingleFile { path } => match self.repo.read_file(&path).await {
+            Actions::ListFileTree => match self.repo.file_tree().await {
+                Ok(entries) => repository::render_tree(&entries),
+                Err(e) => {
+                    println!("Failed to list file tree: {}", e);
+                    format!("Failed to list file tree: {}", e)
+                }
+            },
+            Actions::ReadASingleFile {
+                path,
+                start_line,
+                end_line,
+                max_bytes,
+            } => match self
+                .repo
+                .read_file_range(&path, start_line, end_line, max_bytes)
+                .await
+            {
                 Ok(content) => content,
                 Err(e) => {
                     println!("Failed to read file {}: {}", path, e);
+                    self.report_path_error(&e);
                     format!("Failed to read file {}: {}", path, e)
                 }
             },
-            Actions::RunCommand { command } => {
-                match tokio::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(&command)
-                    .current_dir(self.repo.path())
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        if output.status.success() {
-                            format!(
-                                "Command executed successfully.\nStdout: {}\nStderr: {}",
-                                stdout, stderr
-                            )
-                        } else {
-                            format!(
-                                "Command failed with exit code {:?}.\nStdout: {}\nStderr: {}",
-                                output.status.code(),
-                                stdout,
-                                stderr
-                            )
-                        }
-                    }
-                    Err(e) => {
-                        format!("Failed to execute command '{}': {}", command, e)
-                    }
+            Actions::OutlineFile { path } => match self.repo.read_file(&path).await {
+                Ok(content) => match outline::outline(&path, &content) {
+                    Some(items) if items.is_empty() => format!("No items found in {}", path),
+                    Some(items) => outline::render(&items),
+                    None => format!(
+                        "No outline available for {}, read it with line ranges instead",
+                        path
+                    ),
+                },
+                Err(e) => {
+                    println!("Failed to outline file {}: {}", path, e);
+                    self.report_path_error(&e);
+                    format!("Failed to outline file {}: {}", path, e)
                 }
+            },
+            Actions::SearchCode {
+                pattern,
+                glob,
+                context_lines,
+                max_results,
+            } => match self
+                .repo
+                .search_code(&pattern, glob.as_deref(), context_lines, max_results)
+                .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    println!("Failed to search code for {}: {}", pattern, e);
+                    format!("Failed to search code for {}: {}", pattern, e)
+                }
+            },
+            Actions::GitLog {
+                range,
+                path,
+                max_count,
+            } => self
+                .repo
+                .git_log(range.as_deref(), path.as_deref(), max_count)
+                .unwrap_or_else(|e| {
+                    println!("Failed to list commits: {}", e);
+                    self.report_path_error(&e);
+                    format!("Failed to list commits: {}", e)
+                }),
+            Actions::GitShow {
+                revision,
+                stat_only,
+            } => self
+                .repo
+                .git_show(&revision, stat_only.unwrap_or(false))
+                .unwrap_or_else(|e| {
+                    println!("Failed to show commit {}: {}", revision, e);
+                    format!("Failed to show commit {}: {}", revision, e)
+                }),
+            Actions::GitDiff {
+                from,
+                to,
+                path,
+                stat_only,
+            } => self
+                .repo
+                .git_diff(&from, &to, path.as_deref(), stat_only.unwrap_or(false))
+                .unwrap_or_else(|e| {
+                    println!("Failed to diff {}..{}: {}", from, to, e);
+                    self.report_path_error(&e);
+                    format!("Failed to diff {}..{}: {}", from, to, e)
+                }),
+            Actions::GitBlame {
+                path,
+                start_line,
+                end_line,
+            } => self
+                .repo
+                .git_blame(&path, start_line, end_line)
+                .unwrap_or_else(|e| {
+                    println!("Failed to blame {}: {}", path, e);
+                    self.report_path_error(&e);
+                    format!("Failed to blame {}: {}", path, e)
+                }),
+            Actions::RunCommand { command } => {
+                self.command_policy.run(&command, self.repo.path()).await
             }
             Actions::StoreOrUpdateMemoryInContext { key, value } => {
                 let output = format!("Stored memory: {} = {}", key, value);
                 self.set_memory(key, value);
+                self.persist_state();
                 output
             }
             Actions::RemoveMemoryFromContext { key } => {
                 self.remove_memory(&key);
+                self.persist_state();
                 format!("Removed memory: {}", key)
             }
             Actions::GithubCreateIssue {
@@ -431,9 +1516,9 @@ impl Agent {
                 body,
                 labels,
             } => {
-                let body = format!("{}\nFrom: Pristine.\n\n", body);
+                let body = format!("{}\n{}.\n\n", body, SIGNATURE);
                 match self
-                    .github
+                    .forge
                     .create_issue(title.clone(), body.clone(), labels)
                     .await
                 {
@@ -447,7 +1532,14 @@ impl Agent {
                 }
             }
             Actions::GithubGetIssue { issue_number } => {
-                match self.github.get_issue(issue_number).await {
+                let issue = match self.issues.get(issue_number) {
+                    Some(issue) if self.issues.has_all_comments(issue_number) => Ok(issue.clone()),
+                    _ => self.forge.get_issue(issue_number).await.inspect(|issue| {
+                        self.issues
+                            .set_comments(issue_number, issue.comments.clone())
+                    }),
+                };
+                match issue {
                     Ok(issue) => serde_json::to_string(&issue).unwrap_or_else(|_| {
                         println!("Failed to serialize issue: {}", issue_number);
                         format!("Failed to serialize issue: {}", issue_number)
@@ -462,7 +1554,7 @@ impl Agent {
                 issue_number,
                 label,
             } => {
-                if let Err(e) = self.github.add_label_to_issue(issue_number, &label).await {
+                if let Err(e) = self.forge.add_label_to_issue(issue_number, &label).await {
                     println!(
                         "Failed to add label '{}' to issue #{}: {}",
                         label, issue_number, e
@@ -472,6 +1564,11 @@ impl Agent {
                         label, issue_number, e
                     )
                 } else {
+                    self.issues.update(issue_number, |issue| {
+                        if !issue.labels.contains(&label) {
+                            issue.labels.push(label.clone());
+                        }
+                    });
                     println!("Added label '{}' to issue #{}", label, issue_number);
                     format!("Added label '{}' to issue #{}", label, issue_number)
                 }
@@ -481,7 +1578,7 @@ impl Agent {
                 label,
             } => {
                 if let Err(e) = self
-                    .github
+                    .forge
                     .remove_label_from_issue(issue_number, &label)
                     .await
                 {
@@ -494,37 +1591,49 @@ impl Agent {
                         label, issue_number, e
                     )
                 } else {
+                    self.issues
+                        .update(issue_number, |issue| issue.labels.retain(|l| *l != label));
                     println!("Removed label '{}' from issue #{}", label, issue_number);
                     format!("Removed label '{}' from issue #{}", label, issue_number)
                 }
             }
             Actions::GithubCloseIssue { issue_number } => {
-                if let Err(e) = self.github.close_issue(issue_number).await {
+                if let Err(e) = self.forge.close_issue(issue_number).await {
                     println!("Failed to close issue #{}: {}", issue_number, e);
                     format!("Failed to close issue #{}: {}", issue_number, e)
                 } else {
+                    self.issues.update(issue_number, |issue| {
+                        issue.state = "closed".to_string();
+                        issue.closed_at = Some(Utc::now());
+                    });
+                    self.refresh_issue_context();
                     println!("Closed issue #{}", issue_number);
                     format!("Closed issue #{}", issue_number)
                 }
             }
             Actions::GithubCommentOnIssue { issue_number, body } => {
                 if let Err(e) = self
-                    .github
-                    .comment_on_issue(issue_number, &format!("{body}\nFrom: Pristine"))
+                    .forge
+                    .comment_on_issue(issue_number, &format!("{body}\n{SIGNATURE}"))
                     .await
                 {
                     println!("Failed to comment on issue #{}: {}", issue_number, e);
                     format!("Failed to comment on issue #{}: {}", issue_number, e)
                 } else {
+                    // The stored comments are fetched again on the next read
+                    self.issues
+                        .update(issue_number, |issue| issue.comments_count += 1);
                     println!("Commented on issue #{}: {}", issue_number, body);
                     format!("Commented on issue #{}: {}", issue_number, body)
                 }
             }
             Actions::GithubEditBodyOfIssue { issue_number, body } => {
-                if let Err(e) = self.github.edit_issue_body(issue_number, &body).await {
+                if let Err(e) = self.forge.edit_issue_body(issue_number, &body).await {
                     println!("Failed to edit body of issue #{}: {}", issue_number, e);
                     format!("Failed to edit body of issue #{}: {}", issue_number, e)
                 } else {
+                    self.issues
+                        .update(issue_number, |issue| issue.body = body.clone());
                     println!("Edited body of issue #{}: {}", issue_number, body);
                     format!("Edited body of issue #{}: {}", issue_number, body)
                 }
@@ -533,14 +1642,133 @@ impl Agent {
                 issue_number,
                 title,
             } => {
-                if let Err(e) = self.github.edit_issue_title(issue_number, &title).await {
+                if let Err(e) = self.forge.edit_issue_title(issue_number, &title).await {
                     println!("Failed to edit title of issue #{}: {}", issue_number, e);
                     format!("Failed to edit title of issue #{}: {}", issue_number, e)
                 } else {
+                    self.issues
+                        .update(issue_number, |issue| issue.title = title.clone());
+                    self.refresh_issue_context();
                     println!("Edited title of issue #{}: {}", issue_number, title);
                     format!("Edited title of issue #{}: {}", issue_number, title)
                 }
             }
+            Actions::GithubListPullRequests { state } => {
+                match self.forge.list_pull_requests(Some(state.clone())).await {
+                    Ok(prs) => prs
+                        .iter()
+                        .map(|pr| pr.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n\n"),
+                    Err(e) => {
+                        println!("Failed to list {} pull requests: {}", state, e);
+                        format!("Failed to list {} pull requests: {}", state, e)
+                    }
+                }
+            }
+            Actions::GithubGetPullRequest {
+                pull_request_number,
+            } => match self.forge.get_pull_request(pull_request_number).await {
+                Ok(pr) => {
+                    let files = match self.forge.get_pull_request_files(pull_request_number).await {
+                        Ok(files) => files
+                            .iter()
+                            .map(|f| {
+                                format!(
+                                    "  - {} ({}, +{} -{})",
+                                    f.filename, f.status, f.additions, f.deletions
+                                )
+                            })
+                            .collect::<Vec<String>>()
+                            .join("\n"),
+                        Err(e) => format!("Failed to list changed files: {}", e),
+                    };
+                    format!("{}\nChanged files:\n{}", pr, files)
+                }
+                Err(e) => {
+                    println!("Failed to get pull request #{}: {}", pull_request_number, e);
+                    format!("Failed to get pull request #{}: {}", pull_request_number, e)
+                }
+            },
+            Actions::GithubGetPullRequestDiff {
+                pull_request_number,
+            } => match self.forge.get_pull_request_diff(pull_request_number).await {
+                Ok(diff) => diff,
+                Err(e) => {
+                    println!(
+                        "Failed to get diff of pull request #{}: {}",
+                        pull_request_number, e
+                    );
+                    format!(
+                        "Failed to get diff of pull request #{}: {}",
+                        pull_request_number, e
+                    )
+                }
+            },
+            Actions::GithubGetPullRequestReviewComments {
+                pull_request_number,
+            } => match self
+                .forge
+                .list_pull_request_review_comments(pull_request_number)
+                .await
+            {
+                Ok(comments) if comments.is_empty() => format!(
+                    "No review comments on pull request #{}",
+                    pull_request_number
+                ),
+                Ok(comments) => comments
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{} on {}:{}: {}",
+                            c.author,
+                            c.path,
+                            c.line.map(|l| l.to_string()).unwrap_or_default(),
+                            c.body
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                Err(e) => {
+                    println!(
+                        "Failed to get review comments of pull request #{}: {}",
+                        pull_request_number, e
+                    );
+                    format!(
+                        "Failed to get review comments of pull request #{}: {}",
+                        pull_request_number, e
+                    )
+                }
+            },
+            Actions::GithubCommentOnPullRequest {
+                pull_request_number,
+                body,
+            } => {
+                // Pull requests share the issue comment API
+                if let Err(e) = self
+                    .forge
+                    .comment_on_issue(pull_request_number, &format!("{body}\n{SIGNATURE}"))
+                    .await
+                {
+                    println!(
+                        "Failed to comment on pull request #{}: {}",
+                        pull_request_number, e
+                    );
+                    format!(
+                        "Failed to comment on pull request #{}: {}",
+                        pull_request_number, e
+                    )
+                } else {
+                    println!(
+                        "Commented on pull request #{}: {}",
+                        pull_request_number, body
+                    );
+                    format!(
+                        "Commented on pull request #{}: {}",
+                        pull_request_number, body
+                    )
+                }
+            }
             Actions::RunLLMInference {
                 system_prompt,
                 user_prompt,
@@ -575,3 +1803,154 @@ impl Agent {
         output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(title: &str, body: &str, state: &str, labels: &[&str]) -> forge::Issue {
+        forge::Issue {
+            number: 7,
+            title: title.to_string(),
+            author: "octocat".to_string(),
+            body: body.to_string(),
+            state: state.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            closed_at: None,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            comments: vec![],
+            comments_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_own_comment() {
+        assert!(is_own_comment("Looks fixed.\nFrom: Pristine"));
+        assert!(is_own_comment("Issue body\nFrom: Pristine.\n\n"));
+        assert!(!is_own_comment("Thanks Pristine!"));
+    }
+
+    #[test]
+    fn test_diff_issue_reports_each_change() {
+        let known = issue("Old", "Body", "open", &["bug", "p1"]);
+        let current = issue("New", "Body", "closed", &["bug", "p0"]);
+
+        let events = diff_issue(&known, &current);
+        assert_eq!(
+            events,
+            vec![
+                Event::IssueTitleChanged {
+                    issue_number: 7,
+                    old_title: "Old".to_string(),
+                    new_title: "New".to_string(),
+                },
+                Event::IssueLabelsChanged {
+                    issue_number: 7,
+                    title: "New".to_string(),
+                    added: vec!["p0".to_string()],
+                    removed: vec!["p1".to_string()],
+                },
+                Event::IssueStateChanged {
+                    issue_number: 7,
+                    title: "New".to_string(),
+                    state: "closed".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_issue_unchanged() {
+        let known = issue("Title", "Body", "open", &["bug"]);
+        assert!(diff_issue(&known, &known.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_commit_events_summary() {
+        let commits = (0..30)
+            .map(|i| repository::CommitInfo {
+                hash: format!("{:07}", i),
+                author: "Ada".to_string(),
+                message: format!("Commit {}", i),
+                changed_files: vec![],
+            })
+            .collect();
+        let events = commit_events(repository::NewCommits {
+            branch: "main".to_string(),
+            commits,
+            changed_files: (0..60).map(|i| format!("{}.rs", i)).collect(),
+            truncated: true,
+            rewrite: None,
+        });
+        let [
+            Event::NewCommits {
+                count,
+                commits,
+                changed_files,
+                ..
+            },
+        ] = events.as_slice()
+        else {
+            panic!("expected one summary, got {:?}", events);
+        };
+        assert_eq!(*count, 30);
+        assert_eq!(commits.len(), 2 * SUMMARY_COMMITS + 2);
+        assert_eq!(commits[0], "- ... older commits not listed");
+        assert_eq!(commits[1], "- 0000000 Ada: Commit 0");
+        assert_eq!(commits[SUMMARY_COMMITS + 1], "- ... 20 more commits");
+        assert_eq!(commits.last().unwrap(), "- 0000029 Ada: Commit 29");
+        assert_eq!(changed_files.len(), SUMMARY_FILES + 1);
+        assert_eq!(changed_files.last().unwrap(), "... and 10 more");
+    }
+
+    #[test]
+    fn test_events_persisted_by_older_versions_load() {
+        let comment: Event = serde_json::from_str(
+            r#"{"NewComment": {"issue_number": 3, "author": "ada", "body": "Hi"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            comment,
+            Event::NewComment {
+                issue_number: 3,
+                comment_id: 0,
+                author: "ada".to_string(),
+                body: "Hi".to_string(),
+            }
+        );
+
+        let commits: Event = serde_json::from_str(
+            r#"{"NewCommits": {"from": "a", "to": "b", "commits": [], "changed_files": []}}"#,
+        )
+        .unwrap();
+        assert!(matches!(commits, Event::NewCommits { branch, .. } if branch.is_empty()));
+        let rewritten: Event = serde_json::from_str(
+            r#"{"HistoryRewritten": {"old_head": "a", "new_head": "b", "dropped_commits": []}}"#,
+        )
+        .unwrap();
+        assert!(matches!(rewritten, Event::HistoryRewritten { branch, .. } if branch.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_text_actions() {
+        let thought = "Thoughts:\nClose it.\n\nActions:\n===\n```json\n[{\"github_comment_on_issue\": {\"issue_number\": 1, \"body\": \"Use `x` in:\\n```rust\\nfoo();\\n```\"}}, \"mark_complete\"]\n```\n===\n";
+        assert_eq!(
+            parse_text_actions(thought).unwrap(),
+            vec![
+                Actions::GithubCommentOnIssue {
+                    issue_number: 1,
+                    body: "Use `x` in:\n```rust\nfoo();\n```".to_string(),
+                },
+                Actions::MarkComplete,
+            ]
+        );
+        assert!(parse_text_actions("Nothing to do.").is_err());
+
+        let error =
+            parse_text_actions("===\n[\"mark_complete\", {\"sleep\": {\"duration\": \"5\"}}]\n===")
+                .unwrap_err();
+        assert!(error.contains("actions[1]: sleep.duration: expected integer, got string \"5\""));
+        assert!(parse_text_actions("===\n===").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::actions::Actions;
+
+const APPROVALS_FILE_NAME: &str = "approvals.json";
+
+/// Handled requests decided longer ago than this are dropped from the queue.
+const RETENTION_DAYS: i64 = 30;
+
+/// What happens when the agent decides to take an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActionPolicy {
+    Auto,
+    RequireApproval,
+    Deny,
+}
+
+impl FromStr for ActionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(ActionPolicy::Auto),
+            "require-approval" => Ok(ActionPolicy::RequireApproval),
+            "deny" => Ok(ActionPolicy::Deny),
+            other => Err(anyhow!(
+                "Invalid action policy '{}', expected auto, require-approval or deny",
+                other
+            )),
+        }
+    }
+}
+
+/// Parses `action_name=policy` pairs separated by commas,
+/// e.g. `github_close_issue=require-approval,run_command=deny`.
+pub fn parse_policies(value: &str) -> Result<HashMap<String, ActionPolicy>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, policy) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid action policy entry '{}'", entry))?;
+            Ok((name.trim().to_string(), policy.trim().parse()?))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected { reason: String },
+    Executed { output: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub action: Actions,
+    pub status: ApprovalStatus,
+    /// Whether the agent already ran the approved action or reported the rejection.
+    #[serde(default)]
+    handled: bool,
+}
+
+/// Actions parked until a maintainer approves, edits or rejects them.
+/// Shared between the agent and the web server, like `Monitor`.
+#[derive(Clone, Default)]
+pub struct ApprovalQueue {
+    items: Arc<Mutex<Vec<PendingAction>>>,
+    decided: Arc<Notify>,
+    /// File the queue is written to after every change, `None` keeps it in memory.
+    path: Option<Arc<PathBuf>>,
+}
+
+impl ApprovalQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the queue persisted in the data directory, so requests and
+    /// decisions survive restarts. A missing file yields an empty queue.
+    pub fn open(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let data_dir = data_dir.as_ref();
+        fs::create_dir_all(data_dir).context(format!(
+            "Failed to create data directory {}",
+            data_dir.display()
+        ))?;
+        let path = data_dir.join(APPROVALS_FILE_NAME);
+        let items = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read approvals file {}", path.display()))?;
+            serde_json::from_str(&content)
+                .context(format!("Failed to parse approvals file {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            items: Arc::new(Mutex::new(items)),
+            decided: Arc::default(),
+            path: Some(Arc::new(path)),
+        })
+    }
+
+    /// Parks an action and returns its request ID.
+    pub fn submit(&self, action: Actions) -> u64 {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let id = items.last().map_or(1, |item| item.id + 1);
+        items.push(PendingAction {
+            id,
+            created_at: Utc::now(),
+            decided_at: None,
+            action,
+            status: ApprovalStatus::Pending,
+            handled: false,
+        });
+        Self::prune(&mut items);
+        self.save(&items);
+        id
+    }
+
+    pub fn list(&self) -> Vec<PendingAction> {
+        self.items.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn approve(&self, id: u64) -> Result<()> {
+        self.decide(id, ApprovalStatus::Approved)
+    }
+
+    pub fn reject(&self, id: u64, reason: String) -> Result<()> {
+        self.decide(id, ApprovalStatus::Rejected { reason })
+    }
+
+    /// Replaces a pending action with an edited version of the same kind.
+    pub fn edit(&self, id: u64, action: Actions) -> Result<()> {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let item = Self::pending_mut(&mut items, id)?;
+        if item.action.name() != action.name() {
+            return Err(anyhow!(
+                "Request #{} is a {} action, it cannot become {}",
+                id,
+                item.action.name(),
+                action.name()
+            ));
+        }
+        item.action = action;
+        self.save(&items);
+        Ok(())
+    }
+
+    /// Returns the decisions the agent has not handled yet. They are returned
+    /// again, e.g. after a restart, until marked executed or handled.
+    pub fn decided(&self) -> Vec<PendingAction> {
+        self.items
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|item| item.status != ApprovalStatus::Pending && !item.handled)
+            .cloned()
+            .collect()
+    }
+
+    /// Records the output of an approved action once the agent ran it,
+    /// including the error when it failed.
+    pub fn mark_executed(&self, id: u64, output: String) {
+        self.mark(id, Some(ApprovalStatus::Executed { output }));
+    }
+
+    /// Records that the agent reported a rejection.
+    pub fn mark_handled(&self, id: u64) {
+        self.mark(id, None);
+    }
+
+    /// Resolves once a maintainer decides on a request.
+    pub async fn wait_for_decision(&self) {
+        self.decided.notified().await;
+    }
+
+    fn decide(&self, id: u64, status: ApprovalStatus) -> Result<()> {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let item = Self::pending_mut(&mut items, id)?;
+        item.status = status;
+        item.decided_at = Some(Utc::now());
+        self.save(&items);
+        self.decided.notify_one();
+        Ok(())
+    }
+
+    fn mark(&self, id: u64, status: Option<ApprovalStatus>) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(item) = items.iter_mut().find(|item| item.id == id) {
+            if let Some(status) = status {
+                item.status = status;
+            }
+            item.handled = true;
+        }
+        self.save(&items);
+    }
+
+    /// Drops handled requests decided more than `RETENTION_DAYS` ago. Only
+    /// called after a submission, so the newest ID is kept and never reused.
+    fn prune(items: &mut Vec<PendingAction>) {
+        let cutoff = Utc::now() - chrono::Duration::days(RETENTION_DAYS);
+        items.retain(|item| !item.handled || item.decided_at.is_none_or(|at| at > cutoff));
+    }
+
+    /// Writes the queue atomically through a temporary file. Failures are
+    /// logged, the queue keeps working in memory.
+    fn save(&self, items: &[PendingAction]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_string_pretty(items)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(fs::write(&tmp_path, json)?))
+            .and_then(|()| Ok(fs::rename(&tmp_path, path.as_path())?));
+        if let Err(e) = result {
+            println!("Failed to save approvals to {}: {}", path.display(), e);
+        }
+    }
+
+    fn pending_mut(items: &mut [PendingAction], id: u64) -> Result<&mut PendingAction> {
+        let item = items
+            .iter_mut()
+            .find(|item| item.id == id)
+            .ok_or_else(|| anyhow!("No approval request #{}", id))?;
+        if item.status != ApprovalStatus::Pending {
+            return Err(anyhow!("Approval request #{} was already decided", id));
+        }
+        Ok(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policies() {
+        let policies =
+            parse_policies("github_close_issue=require-approval, run_command=deny").unwrap();
+        assert_eq!(
+            policies.get("github_close_issue"),
+            Some(&ActionPolicy::RequireApproval)
+        );
+        assert_eq!(policies.get("run_command"), Some(&ActionPolicy::Deny));
+        assert!(parse_policies("").unwrap().is_empty());
+        assert!(parse_policies("run_command=maybe").is_err());
+    }
+
+    #[test]
+    fn test_queue_lifecycle() {
+        let queue = ApprovalQueue::new();
+        let close = queue.submit(Actions::GithubCloseIssue { issue_number: 1 });
+        let create = queue.submit(Actions::GithubCreateIssue {
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+            labels: vec![],
+        });
+        assert!(queue.decided().is_empty());
+
+        // Edits must keep the action kind
+        assert!(
+            queue
+                .edit(close, Actions::GithubCloseIssue { issue_number: 2 })
+                .is_ok()
+        );
+        assert!(queue.edit(close, Actions::MarkComplete).is_err());
+
+        queue.approve(close).unwrap();
+        queue.reject(create, "Duplicate of #3".to_string()).unwrap();
+        assert!(queue.approve(close).is_err());
+
+        let decided = queue.decided();
+        assert_eq!(decided.len(), 2);
+        assert_eq!(
+            decided[0].action,
+            Actions::GithubCloseIssue { issue_number: 2 }
+        );
+        assert_eq!(
+            decided[1].status,
+            ApprovalStatus::Rejected {
+                reason: "Duplicate of #3".to_string()
+            }
+        );
+
+        // Decisions stay pending for the agent until it ran or reported them
+        assert_eq!(queue.decided().len(), 2);
+        queue.mark_executed(close, "Closed issue #2".to_string());
+        queue.mark_handled(create);
+        assert!(queue.decided().is_empty());
+        assert_eq!(
+            queue.list()[0].status,
+            ApprovalStatus::Executed {
+                output: "Closed issue #2".to_string()
+            }
+        );
+
+        // Handled requests are dropped after the retention window
+        queue.items.lock().unwrap()[1].decided_at =
+            Some(Utc::now() - chrono::Duration::days(RETENTION_DAYS + 1));
+        queue.submit(Actions::MarkComplete);
+        let ids: Vec<u64> = queue.list().iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![close, create + 1]);
+    }
+
+    #[test]
+    fn test_queue_persistence() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let queue = ApprovalQueue::open(dir.path()).unwrap();
+        let pending = queue.submit(Actions::GithubCloseIssue { issue_number: 1 });
+        let approved = queue.submit(Actions::GithubCloseIssue { issue_number: 2 });
+        queue.approve(approved).unwrap();
+
+        // An approval the agent did not run yet survives a restart
+        let reopened = ApprovalQueue::open(dir.path()).unwrap();
+        assert_eq!(reopened.list().len(), 2);
+        assert_eq!(reopened.decided()[0].id, approved);
+        reopened.mark_executed(approved, "Closed issue #2".to_string());
+
+        // Executed decisions are not run again
+        let reopened = ApprovalQueue::open(dir.path()).unwrap();
+        assert!(reopened.decided().is_empty());
+        reopened.approve(pending).unwrap();
+        assert_eq!(reopened.decided()[0].id, pending);
+    }
+}
@@ -4,14 +4,25 @@ use pristine::web_server::WebServer;
 
 #[tokio::main]
 async fn main() {
+    // Errors unwrapped below may carry a configured secret
+    pristine::redact::install_panic_hook();
     let _ = dotenvy::dotenv_override();
     let config =
         Config::from_env().expect("Failed to load configuration from environment variables");
-    let agent = Agent::new(&config).await.expect("Failed to create agent");
+    let mut agent = Agent::new(&config).await.expect("Failed to create agent");
 
     // Get the monitor from the agent and start the web server
     let monitor = agent.get_monitor();
-    let web_server = WebServer::new(monitor, 5005);
+    let mut web_server = WebServer::new(monitor, 5005)
+        .with_approvals(agent.get_approval_queue(), config.admin_token.clone());
+
+    // Forward verified webhook deliveries to the agent
+    if let Some(secret) = &config.github_webhook_secret {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        agent.set_webhook_receiver(receiver);
+        web_server =
+            web_server.with_webhook(config.github_webhook_path.clone(), secret.clone(), sender);
+    }
 
     // Start the web server in the background
     web_server
@@ -1,24 +1,159 @@
+use std::collections::HashMap;
+
+use strum::IntoEnumIterator;
+
+use crate::actions::Actions;
+use crate::approval::{self, ActionPolicy};
+use crate::redact;
+use crate::sandbox;
+
 #[derive(Clone)] // Add Clone trait
 #[allow(dead_code)]
 pub struct Config {
-    pub github_personal_access_token: String,
+    /// Hosting the repository: "github" or "gitea", which also covers Forgejo.
+    pub forge: String,
+    /// Base URL of the Gitea instance, such as `https://gitea.example.com`.
+    pub gitea_url: Option<String>,
+    pub gitea_token: Option<String>,
+
+    /// Unset when authenticating as a GitHub App.
+    pub github_personal_access_token: Option<String>,
+    pub github_app: Option<GitHubAppConfig>,
 
     pub openai_api_key: String,
     pub openai_api_base: String,
     pub openai_api_type: String,
     pub openai_api_model: String,
+    pub openai_tool_calling: bool,
 
     pub github_repository_owner: String,
     pub github_repository_name: String,
     pub github_repository_issues_branch: String,
+    /// Branches watched besides the checked out one, as names or globs.
+    pub watch_branches: Vec<String>,
+    /// Overrides the GitHub clone URL, e.g. a `file://` remote for offline testing.
+    pub clone_url: Option<String>,
+    /// Reused across runs instead of cloning into a temporary directory.
+    pub workspace_dir: Option<String>,
+    pub clone_depth: Option<u32>,
+    pub clone_filter: Option<String>,
+
+    pub github_webhook_secret: Option<String>,
+    pub github_webhook_path: String,
+    pub poll_interval_secs: u64,
+
+    pub command_allow: Vec<String>,
+    pub command_deny: Vec<String>,
+    pub command_timeout_secs: u64,
+    pub command_max_output_bytes: usize,
+    pub command_sandbox: String,
+
+    pub dry_run: bool,
+    pub action_policies: HashMap<String, ActionPolicy>,
+    /// Bearer token required by the dashboard's approval endpoints.
+    pub admin_token: Option<String>,
+
+    pub data_dir: String,
+}
+
+/// Credentials of a GitHub App installed on the repository.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GitHubAppConfig {
+    pub app_id: u64,
+    pub private_key_path: String,
+    pub installation_id: u64,
+}
+
+impl GitHubAppConfig {
+    /// Reads `GITHUB_APP_ID`, `GITHUB_APP_PRIVATE_KEY_PATH` and
+    /// `GITHUB_APP_INSTALLATION_ID`, which must be set together.
+    fn from_env() -> anyhow::Result<Option<Self>> {
+        let app_id = optional_var("GITHUB_APP_ID");
+        let private_key_path = optional_var("GITHUB_APP_PRIVATE_KEY_PATH");
+        let installation_id = optional_var("GITHUB_APP_INSTALLATION_ID");
+        match (app_id, private_key_path, installation_id) {
+            (None, None, None) => Ok(None),
+            (Some(app_id), Some(private_key_path), Some(installation_id)) => Ok(Some(Self {
+                app_id: app_id
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse GITHUB_APP_ID: {}", e))?,
+                private_key_path,
+                installation_id: installation_id.parse().map_err(|e| {
+                    anyhow::anyhow!("Failed to parse GITHUB_APP_INSTALLATION_ID: {}", e)
+                })?,
+            })),
+            _ => Err(anyhow::anyhow!(
+                "GITHUB_APP_ID, GITHUB_APP_PRIVATE_KEY_PATH and GITHUB_APP_INSTALLATION_ID must be set together"
+            )),
+        }
+    }
 }
 
 impl Config {
     pub fn from_env() -> anyhow::Result<Self> {
-        Ok(Self {
-            github_personal_access_token: std::env::var("GITHUB_PERSONAL_ACCESS_TOKEN").map_err(
-                |e| anyhow::anyhow!("Failed to read GITHUB_PERSONAL_ACCESS_TOKEN: {}", e),
-            )?,
+        let github_webhook_secret = std::env::var("GITHUB_WEBHOOK_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty());
+        // With webhooks, polling is only a fallback for missed deliveries.
+        let default_poll_interval = if github_webhook_secret.is_some() {
+            300
+        } else {
+            5
+        };
+
+        let action_policies =
+            approval::parse_policies(&std::env::var("PRISTINE_ACTION_POLICY").unwrap_or_default())
+                .map_err(|e| anyhow::anyhow!("Failed to parse PRISTINE_ACTION_POLICY: {}", e))?;
+        if let Some(unknown) = action_policies
+            .keys()
+            .find(|name| !Actions::iter().any(|a| a.name() == name.as_str()))
+        {
+            return Err(anyhow::anyhow!(
+                "Unknown action '{}' in PRISTINE_ACTION_POLICY",
+                unknown
+            ));
+        }
+
+        let forge = optional_var("PRISTINE_FORGE")
+            .unwrap_or_else(|| "github".to_string())
+            .to_lowercase();
+        let gitea_url = optional_var("GITEA_URL");
+        let gitea_token = optional_var("GITEA_TOKEN");
+        let github_personal_access_token = optional_var("GITHUB_PERSONAL_ACCESS_TOKEN");
+        let github_app = GitHubAppConfig::from_env()?;
+        match forge.as_str() {
+            "github" if github_personal_access_token.is_none() && github_app.is_none() => {
+                return Err(anyhow::anyhow!(
+                    "Set GITHUB_PERSONAL_ACCESS_TOKEN or the GITHUB_APP_* variables"
+                ));
+            }
+            "github" => {}
+            "gitea" if gitea_url.is_none() || gitea_token.is_none() => {
+                return Err(anyhow::anyhow!(
+                    "Set GITEA_URL and GITEA_TOKEN to use PRISTINE_FORGE=gitea"
+                ));
+            }
+            "gitea" if github_webhook_secret.is_some() => {
+                return Err(anyhow::anyhow!(
+                    "Webhooks are GitHub-only, unset GITHUB_WEBHOOK_SECRET to use PRISTINE_FORGE=gitea"
+                ));
+            }
+            "gitea" => {}
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unknown PRISTINE_FORGE '{}', expected github or gitea",
+                    other
+                ));
+            }
+        }
+
+        let config = Self {
+            forge,
+            gitea_url,
+            gitea_token,
+
+            github_personal_access_token,
+            github_app,
 
             openai_api_key: std::env::var("OPENAI_API_KEY")
                 .map_err(|e| anyhow::anyhow!("Failed to read OPENAI_API_KEY: {}", e))?,
@@ -27,6 +162,9 @@ impl Config {
             openai_api_type: "openai".to_string(),
             openai_api_model: std::env::var("OPENAI_API_MODEL")
                 .unwrap_or_else(|_| "gpt-3.5-turbo".to_string()),
+            openai_tool_calling: std::env::var("OPENAI_TOOL_CALLING")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
 
             github_repository_owner: std::env::var("GITHUB_REPOSITORY_OWNER")
                 .map_err(|e| anyhow::anyhow!("Failed to read GITHUB_REPOSITORY_OWNER: {}", e))?,
@@ -34,6 +172,90 @@ impl Config {
                 .map_err(|e| anyhow::anyhow!("Failed to read GITHUB_REPOSITORY_NAME: {}", e))?,
             github_repository_issues_branch: std::env::var("GITHUB_REPOSITORY_ISSUES_BRANCH")
                 .unwrap_or_else(|_| "issues".to_string()),
-        })
+            watch_branches: list_var("PRISTINE_WATCH_BRANCHES", &[]),
+            clone_url: optional_var("PRISTINE_CLONE_URL"),
+            workspace_dir: optional_var("PRISTINE_WORKSPACE_DIR"),
+            clone_depth: match optional_var("PRISTINE_CLONE_DEPTH") {
+                Some(v) => {
+                    Some(v.parse().map_err(|e| {
+                        anyhow::anyhow!("Failed to parse PRISTINE_CLONE_DEPTH: {}", e)
+                    })?)
+                }
+                None => None,
+            },
+            clone_filter: optional_var("PRISTINE_CLONE_FILTER"),
+
+            github_webhook_secret,
+            github_webhook_path: std::env::var("GITHUB_WEBHOOK_PATH")
+                .unwrap_or_else(|_| "/webhook".to_string()),
+            poll_interval_secs: match std::env::var("PRISTINE_POLL_INTERVAL_SECS") {
+                Ok(v) => v.parse().map_err(|e| {
+                    anyhow::anyhow!("Failed to parse PRISTINE_POLL_INTERVAL_SECS: {}", e)
+                })?,
+                Err(_) => default_poll_interval,
+            },
+
+            command_allow: list_var("PRISTINE_COMMAND_ALLOW", sandbox::DEFAULT_ALLOWED_PROGRAMS),
+            command_deny: list_var("PRISTINE_COMMAND_DENY", sandbox::DEFAULT_DENIED_PROGRAMS),
+            command_timeout_secs: match std::env::var("PRISTINE_COMMAND_TIMEOUT_SECS") {
+                Ok(v) => v.parse().map_err(|e| {
+                    anyhow::anyhow!("Failed to parse PRISTINE_COMMAND_TIMEOUT_SECS: {}", e)
+                })?,
+                Err(_) => 30,
+            },
+            command_max_output_bytes: match std::env::var("PRISTINE_COMMAND_MAX_OUTPUT_BYTES") {
+                Ok(v) => v.parse().map_err(|e| {
+                    anyhow::anyhow!("Failed to parse PRISTINE_COMMAND_MAX_OUTPUT_BYTES: {}", e)
+                })?,
+                Err(_) => 64 * 1024,
+            },
+            command_sandbox: std::env::var("PRISTINE_COMMAND_SANDBOX")
+                .unwrap_or_else(|_| "auto".to_string()),
+
+            dry_run: std::env::var("PRISTINE_DRY_RUN")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            action_policies,
+            admin_token: optional_var("PRISTINE_ADMIN_TOKEN"),
+
+            data_dir: std::env::var("PRISTINE_DATA_DIR")
+                .unwrap_or_else(|_| ".pristine".to_string()),
+        };
+        config.register_secrets();
+        Ok(config)
+    }
+
+    /// Masks every configured secret from logs, the dashboard, prompts and command output.
+    pub fn register_secrets(&self) {
+        if let Some(token) = &self.github_personal_access_token {
+            redact::register(token);
+        }
+        if let Some(token) = &self.gitea_token {
+            redact::register(token);
+        }
+        redact::register(&self.openai_api_key);
+        if let Some(secret) = &self.github_webhook_secret {
+            redact::register(secret);
+        }
+        if let Some(token) = &self.admin_token {
+            redact::register(token);
+        }
+    }
+}
+
+/// Reads a variable, treating an empty value like an unset one.
+fn optional_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Reads a comma separated list, falling back to `default` when the variable is unset.
+fn list_var(name: &str, default: &[&str]) -> Vec<String> {
+    match std::env::var(name) {
+        Ok(v) => v
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Err(_) => default.iter().map(|item| item.to_string()).collect(),
     }
 }
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::actions::Actions;
+use crate::monitoring::Monitor;
+
+/// Stands in for the forge when `PRISTINE_DRY_RUN` is set: mutating actions are
+/// answered with the result the real call would have produced, and logged to the
+/// `Monitor` instead of being sent.
+pub struct DryRunRecorder {
+    next_issue_number: u64,
+    monitor: Arc<Monitor>,
+}
+
+impl DryRunRecorder {
+    /// `next_issue_number` should be above every existing issue and pull request,
+    /// so synthetic numbers never point at real ones.
+    pub fn new(next_issue_number: u64, monitor: Arc<Monitor>) -> Self {
+        Self {
+            next_issue_number,
+            monitor,
+        }
+    }
+
+    /// Returns the output the action would have had, mirroring `Agent::act`.
+    pub fn record(&mut self, action: &Actions) -> String {
+        let output = match action {
+            Actions::GithubCreateIssue { title, .. } => {
+                let issue_number = self.next_issue_number;
+                self.next_issue_number += 1;
+                format!("Created issue: {} - {}", issue_number, title)
+            }
+            Actions::GithubAddLabelToIssue {
+                issue_number,
+                label,
+            } => format!("Added label '{}' to issue #{}", label, issue_number),
+            Actions::GithubRemoveLabelFromIssue {
+                issue_number,
+                label,
+            } => format!("Removed label '{}' from issue #{}", label, issue_number),
+            Actions::GithubCloseIssue { issue_number } => format!("Closed issue #{}", issue_number),
+            Actions::GithubCommentOnIssue { issue_number, body } => {
+                format!("Commented on issue #{}: {}", issue_number, body)
+            }
+            Actions::GithubEditBodyOfIssue { issue_number, body } => {
+                format!("Edited body of issue #{}: {}", issue_number, body)
+            }
+            Actions::GithubEditTitleOfIssue {
+                issue_number,
+                title,
+            } => format!("Edited title of issue #{}: {}", issue_number, title),
+            Actions::GithubCommentOnPullRequest {
+                pull_request_number,
+                body,
+            } => format!(
+                "Commented on pull request #{}: {}",
+                pull_request_number, body
+            ),
+            other => format!("Dry run: {} was not executed", other.name()),
+        };
+
+        println!("[dry run] {}", output);
+        self.monitor.log_dry_run(action.clone(), output.clone());
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_uses_synthetic_issue_numbers() {
+        let monitor = Arc::new(Monitor::new());
+        let mut recorder = DryRunRecorder::new(42, monitor.clone());
+        let create = |title: &str| Actions::GithubCreateIssue {
+            title: title.to_string(),
+            body: "Body".to_string(),
+            labels: vec![],
+        };
+
+        assert_eq!(recorder.record(&create("A")), "Created issue: 42 - A");
+        assert_eq!(recorder.record(&create("B")), "Created issue: 43 - B");
+        assert_eq!(
+            recorder.record(&Actions::GithubCloseIssue { issue_number: 3 }),
+            "Closed issue #3"
+        );
+
+        let logs = monitor.get_dry_run_logs();
+        assert_eq!(logs.len(), 3);
+        assert_eq!(
+            logs[2].action,
+            Actions::GithubCloseIssue { issue_number: 3 }
+        );
+    }
+}
@@ -0,0 +1,547 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use git2::Repository;
+use http::StatusCode;
+use http::header::HeaderMap;
+use tempfile::TempDir;
+use tokio::time::sleep;
+
+use crate::config::Config;
+use crate::gitea::GiteaClient;
+use crate::github::{GitHubClient, RateLimit};
+use crate::monitoring::Monitor;
+use crate::repository::{self, CloneOptions, Workspace};
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub body: String,
+    pub state: String,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub closed_at: Option<DateTime<Utc>>,
+    pub labels: Vec<String>,
+    pub comments: Vec<(String, String)>, // (author, comment_body)
+    pub comments_count: usize,           // Added to track the number of comments
+}
+
+impl Display for Issue {
+    /// Formats the issue for adding to the context prompt.
+    /// We add all the relevant fields to the prompt.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formatted_comments: Vec<String> = self
+            .comments
+            .iter()
+            .map(|(author, body)| format!("  - {}: {}", author, body))
+            .collect();
+
+        write!(
+            f,
+            "Issue #{}: {}\nAuthor: {}\nState: {}\nUpdated at: {}\nLabels: {:?}\nBody: {}\nComments and Updates:\n{}",
+            self.number,
+            self.title,
+            self.author,
+            self.state,
+            self.updated_at.to_rfc3339(),
+            self.labels,
+            self.body,
+            formatted_comments.join("\n")
+        )
+    }
+}
+
+/// A comment on an issue or pull request.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Comment {
+    pub id: u64,
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub body: String,
+    pub state: String, // open, closed or merged
+    pub head_branch: String,
+    pub base_branch: String,
+    pub updated_at: DateTime<Utc>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub linked_issues: Vec<u64>, // Issues referenced with "Fixes #N" and similar keywords
+}
+
+impl Display for PullRequest {
+    /// Formats the pull request for adding to the context prompt.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Pull request #{}: {}\nAuthor: {}\nState: {}\nBranch: {} -> {}\nUpdated at: {}\nLinked issues: {:?}\nBody: {}",
+            self.number,
+            self.title,
+            self.author,
+            self.state,
+            self.head_branch,
+            self.base_branch,
+            self.updated_at.to_rfc3339(),
+            self.linked_issues,
+            self.body
+        )
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PullRequestFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ReviewComment {
+    pub author: String,
+    pub path: String,
+    pub line: Option<u64>,
+    pub body: String,
+}
+
+const CLOSING_KEYWORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+/// Extracts the issue numbers a pull request closes, e.g. "Fixes #12" or "resolves: #3".
+pub fn linked_issues(text: &str) -> Vec<u64> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let mut issues = Vec::new();
+    for pair in words.windows(2) {
+        let keyword = pair[0].trim_end_matches(':');
+        if !CLOSING_KEYWORDS.contains(&keyword) {
+            continue;
+        }
+        let Some(reference) = pair[1].strip_prefix('#') else {
+            continue;
+        };
+        let digits: String = reference
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if let Ok(number) = digits.parse::<u64>()
+            && !issues.contains(&number)
+        {
+            issues.push(number);
+        }
+    }
+    issues
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Release {
+    pub id: u64,
+    pub tag_name: String,
+    pub name: String,
+    pub author: String,
+    pub body: String,
+    pub prerelease: bool,
+    pub linked_issues: Vec<u64>, // Issues referenced with "Fixes #N" in the release notes
+}
+
+/// Issues changed since a previous listing, see `Forge::list_issues_updated_since`.
+#[derive(Debug, Clone)]
+pub struct IssueUpdates {
+    /// `None` when nothing changed since the request that returned the ETag.
+    pub issues: Option<Vec<Issue>>,
+    /// Sent back as `If-None-Match` by the next listing, where the forge supports it.
+    pub etag: Option<String>,
+    /// Latest `updated_at` of the listed issues and pull requests, the next `since`.
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+/// A code forge hosting the repository: its issues, comments, labels, pull
+/// (or merge) requests and releases, and where to clone it from.
+#[async_trait]
+pub trait Forge: Send + Sync {
+    /// URL to clone and fetch from, carrying a currently valid token.
+    async fn clone_url(&self) -> Result<String>;
+
+    /// Clones the repository into the configured workspace, or a temporary
+    /// directory. An existing clone in the workspace is reused.
+    async fn clone_repository(&self, config: &Config) -> Result<(Workspace, Repository)> {
+        let clone_url = self.clone_url().await?;
+        println!("Clone URL: {}", repository::without_credentials(&clone_url));
+
+        let workspace = match &config.workspace_dir {
+            Some(dir) => Workspace::Persistent(PathBuf::from(dir)),
+            None => Workspace::Temporary(TempDir::new().map_err(|e| {
+                anyhow::anyhow!("Failed to create temporary directory for repo: {}", e)
+            })?),
+        };
+        let repo =
+            repository::open_or_clone(&clone_url, &workspace, &CloneOptions::from_config(config))?;
+
+        println!("Repository ready in {}", workspace.path().display());
+        Ok((workspace, repo))
+    }
+
+    /// Lists the issues updated since `since`, or all of them. When `etag`
+    /// still matches, nothing changed and `issues` is `None`.
+    async fn list_issues_updated_since(
+        &self,
+        since: Option<DateTime<Utc>>,
+        etag: Option<&str>,
+    ) -> Result<IssueUpdates>;
+
+    /// Gets an issue with all its comments.
+    async fn get_issue(&self, issue_number: u64) -> Result<Issue>;
+
+    async fn get_issue_comments(&self, issue_number: u64) -> Result<Vec<Comment>>;
+
+    /// Opens an issue and returns its number.
+    async fn create_issue(&self, title: String, body: String, labels: Vec<String>) -> Result<u64>;
+
+    async fn add_label_to_issue(&self, issue_number: u64, label: &str) -> Result<()>;
+
+    async fn remove_label_from_issue(&self, issue_number: u64, label: &str) -> Result<()>;
+
+    async fn close_issue(&self, issue_number: u64) -> Result<()>;
+
+    /// Comments on an issue or pull request.
+    async fn comment_on_issue(&self, issue_number: u64, body: &str) -> Result<()>;
+
+    async fn edit_issue_body(&self, issue_number: u64, body: &str) -> Result<()>;
+
+    async fn edit_issue_title(&self, issue_number: u64, title: &str) -> Result<()>;
+
+    /// Lists pull requests in the given state: "open", "closed", "merged" or "all".
+    async fn list_pull_requests(&self, state: Option<String>) -> Result<Vec<PullRequest>>;
+
+    /// Lists the most recently updated pull requests, in any state.
+    /// One page is enough to catch everything that changed since the previous poll.
+    async fn list_recent_pull_requests(&self) -> Result<Vec<PullRequest>>;
+
+    async fn get_pull_request(&self, pull_request_number: u64) -> Result<PullRequest>;
+
+    async fn get_pull_request_files(
+        &self,
+        pull_request_number: u64,
+    ) -> Result<Vec<PullRequestFile>>;
+
+    async fn get_pull_request_diff(&self, pull_request_number: u64) -> Result<String>;
+
+    async fn list_pull_request_review_comments(
+        &self,
+        pull_request_number: u64,
+    ) -> Result<Vec<ReviewComment>>;
+
+    /// Lists the most recent published releases, drafts excluded.
+    async fn list_recent_releases(&self) -> Result<Vec<Release>>;
+
+    /// The API quota last reported by the forge, if it has one.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+
+    /// Reports forge state, such as the API quota, to the dashboard.
+    fn set_monitor(&mut self, _monitor: Arc<Monitor>) {}
+}
+
+/// Connects to the forge selected by `PRISTINE_FORGE`.
+pub async fn connect(config: &Config) -> Result<Box<dyn Forge>> {
+    match config.forge.as_str() {
+        "gitea" => Ok(Box::new(GiteaClient::new(config)?)),
+        _ => Ok(Box::new(GitHubClient::new(config).await?)),
+    }
+}
+
+/// Attempts made for a forge call, the first one included.
+const MAX_ATTEMPTS: u32 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+/// GitHub asks to wait at least a minute after a secondary rate limit.
+const SECONDARY_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+/// Longer `Retry-After` waits are reported instead of blocking the agent.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Why a forge API call failed, which decides whether it is retried and
+/// what the model is told to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForgeErrorKind {
+    NotFound,
+    Permission,
+    RateLimited,
+    /// The request was rejected as invalid, e.g. a 422 for an unknown label.
+    Validation,
+    /// Network errors and 5xx responses, which may succeed later.
+    Transient,
+    Other,
+}
+
+impl ForgeErrorKind {
+    /// Next step suggested to the model along with the error.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::NotFound => Some("it does not exist, check the number or name"),
+            Self::Permission => Some("the token is not allowed to do this, do not retry it"),
+            Self::RateLimited => Some("the rate limit was hit, wait before calling the API again"),
+            Self::Validation => Some("the arguments were rejected, fix them before retrying"),
+            Self::Transient => Some("the API is unavailable right now, retrying later may work"),
+            Self::Other => None,
+        }
+    }
+}
+
+/// A failed forge API call, classified. It is the root cause of the `anyhow`
+/// errors returned by every `Forge`, so callers can downcast to it.
+#[derive(Debug, Clone)]
+pub struct ForgeCallError {
+    pub kind: ForgeErrorKind,
+    /// What was being done, such as "Failed to close issue #3".
+    pub action: String,
+    pub message: String,
+    /// Set for secondary rate limits, which GitHub lifts after a minute.
+    secondary_rate_limit: bool,
+    /// The response's `Retry-After`, when it was seen.
+    retry_after: Option<Duration>,
+}
+
+impl ForgeCallError {
+    /// An error without a response status, such as a network failure.
+    pub(crate) fn new(kind: ForgeErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            action: String::new(),
+            message,
+            secondary_rate_limit: false,
+            retry_after: None,
+        }
+    }
+
+    pub fn from_status(status: StatusCode) -> Self {
+        Self::classify(status, status.to_string())
+    }
+
+    pub(crate) fn classify(status: StatusCode, message: String) -> Self {
+        let lower = message.to_lowercase();
+        let secondary_rate_limit = status == StatusCode::TOO_MANY_REQUESTS
+            || lower.contains("secondary rate limit")
+            || lower.contains("abuse");
+        let kind = match status.as_u16() {
+            404 | 410 => ForgeErrorKind::NotFound,
+            429 => ForgeErrorKind::RateLimited,
+            403 if secondary_rate_limit || lower.contains("rate limit") => {
+                ForgeErrorKind::RateLimited
+            }
+            401 | 403 => ForgeErrorKind::Permission,
+            400..=499 => ForgeErrorKind::Validation,
+            500..=599 => ForgeErrorKind::Transient,
+            _ => ForgeErrorKind::Other,
+        };
+        Self {
+            kind,
+            action: String::new(),
+            message,
+            secondary_rate_limit,
+            retry_after: None,
+        }
+    }
+
+    /// Records the `Retry-After` header of the failed response, given in
+    /// seconds or as an HTTP date.
+    pub(crate) fn with_retry_after(mut self, headers: &HeaderMap) -> Self {
+        self.retry_after = headers
+            .get("retry-after")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                if let Ok(seconds) = value.trim().parse::<u64>() {
+                    return Some(Duration::from_secs(seconds));
+                }
+                let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+                (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+            });
+        self
+    }
+
+    fn during(mut self, action: &str) -> Self {
+        self.action = action.to_string();
+        self
+    }
+
+    /// Base delay before retrying, `None` when retrying cannot help.
+    /// Calls that are not `idempotent` are only retried when the forge refused them.
+    fn retry_delay(&self, idempotent: bool) -> Option<Duration> {
+        if self
+            .retry_after
+            .is_some_and(|after| after > MAX_RETRY_AFTER)
+        {
+            return None;
+        }
+        match self.kind {
+            ForgeErrorKind::RateLimited if self.secondary_rate_limit => {
+                Some(SECONDARY_RATE_LIMIT_DELAY)
+            }
+            ForgeErrorKind::Transient if idempotent => Some(RETRY_BASE_DELAY),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ForgeCallError {
+    fn from(error: reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            return Self::classify(status, error.to_string());
+        }
+        let kind = if error.is_connect() || error.is_timeout() || error.is_request() {
+            ForgeErrorKind::Transient
+        } else {
+            ForgeErrorKind::Other
+        };
+        Self::new(kind, error.to_string())
+    }
+}
+
+impl Display for ForgeCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.action, self.message)?;
+        if let Some(hint) = self.kind.hint() {
+            write!(f, " ({})", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ForgeCallError {}
+
+/// Runs a forge API call, retrying network errors, 5xx responses and
+/// secondary rate limits with exponential backoff and jitter. Other errors are
+/// returned at once as a `ForgeCallError` describing `action`.
+pub(crate) async fn with_retries<T, E, F, Fut>(
+    action: &str,
+    idempotent: bool,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: Into<ForgeCallError>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match call().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e.into().during(action),
+        };
+        let delay = match error.retry_delay(idempotent) {
+            Some(base) if attempt < MAX_ATTEMPTS => {
+                error.retry_after.unwrap_or_else(|| backoff(base, attempt))
+            }
+            _ => return Err(error.into()),
+        };
+        println!(
+            "{} (attempt {}/{}), retrying in {:?}",
+            error, attempt, MAX_ATTEMPTS, delay
+        );
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// `base` doubled for each previous attempt, with the upper half randomised so
+/// that concurrent clients do not retry in lockstep.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let delay = base * 2u32.pow(attempt - 1);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    delay / 2 + delay.mul_f64(f64::from(nanos % 1000) / 2000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderValue;
+
+    #[test]
+    fn test_linked_issues() {
+        assert_eq!(linked_issues("Fixes #12"), vec![12]);
+        assert_eq!(
+            linked_issues("This PR closes #3, resolves: #4 and fixes #3."),
+            vec![3, 4]
+        );
+        assert!(linked_issues("Related to #5, see issue #6").is_empty());
+        assert!(linked_issues("Fixes the bug in #parser").is_empty());
+    }
+
+    #[test]
+    fn test_classify_errors() {
+        let classify =
+            |status, message: &str| ForgeCallError::classify(status, message.to_string());
+
+        let not_found = classify(StatusCode::NOT_FOUND, "Not Found");
+        assert_eq!(not_found.kind, ForgeErrorKind::NotFound);
+        assert_eq!(not_found.retry_delay(true), None);
+
+        let secondary = classify(
+            StatusCode::FORBIDDEN,
+            "You have exceeded a secondary rate limit",
+        );
+        assert_eq!(secondary.kind, ForgeErrorKind::RateLimited);
+        assert_eq!(
+            secondary.retry_delay(false),
+            Some(SECONDARY_RATE_LIMIT_DELAY)
+        );
+        let primary = classify(StatusCode::FORBIDDEN, "API rate limit exceeded for user");
+        assert_eq!(primary.kind, ForgeErrorKind::RateLimited);
+        assert_eq!(primary.retry_delay(true), None);
+        let permission = classify(
+            StatusCode::FORBIDDEN,
+            "Resource not accessible by personal access token",
+        );
+        assert_eq!(permission.kind, ForgeErrorKind::Permission);
+
+        let validation = classify(StatusCode::UNPROCESSABLE_ENTITY, "Validation Failed");
+        assert_eq!(validation.kind, ForgeErrorKind::Validation);
+        assert_eq!(validation.retry_delay(true), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("30"));
+        let secondary = secondary.with_retry_after(&headers);
+        assert_eq!(secondary.retry_after, Some(Duration::from_secs(30)));
+        assert!(secondary.retry_delay(false).is_some());
+        headers.insert("retry-after", HeaderValue::from_static("3600"));
+        assert_eq!(
+            secondary.with_retry_after(&headers).retry_delay(false),
+            None
+        );
+
+        let transient = ForgeCallError::from_status(StatusCode::BAD_GATEWAY);
+        assert_eq!(transient.retry_delay(true), Some(RETRY_BASE_DELAY));
+        // A create may have gone through before the 502
+        assert_eq!(transient.retry_delay(false), None);
+        assert_eq!(
+            transient.during("Failed to close issue #3").to_string(),
+            "Failed to close issue #3: 502 Bad Gateway (the API is unavailable right now, retrying later may work)"
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        for attempt in 1..=3 {
+            let full = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = backoff(RETRY_BASE_DELAY, attempt);
+            assert!(delay >= full / 2 && delay <= full);
+        }
+    }
+}
@@ -1,125 +1,373 @@
 use crate::config::Config;
+use crate::forge::{
+    Comment, Forge, ForgeCallError, ForgeErrorKind, Issue, IssueUpdates, PullRequest,
+    PullRequestFile, Release, ReviewComment, linked_issues, with_retries,
+};
+use crate::monitoring::Monitor;
+use crate::redact;
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc}; // Use chrono directly
-use git2::Repository;
-use octocrab::Octocrab;
-use octocrab::models::{IssueState, issues::Comment};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc}; // Use chrono directly
+use http::StatusCode;
+use http::header::{ETAG, HeaderMap, HeaderValue, IF_NONE_MATCH};
+use octocrab::models::{AppId, InstallationId, InstallationToken, IssueState};
 use octocrab::params::Direction;
-use octocrab::params::issues::Sort::{self, Updated}; // For sorting issues
-use std::fmt::Display;
-use tempfile::TempDir;
-
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
-pub struct Issue {
-    pub number: u64,
-    pub title: String,
-    pub body: String,
-    pub state: String,
-    pub updated_at: DateTime<Utc>,
-    pub labels: Vec<String>,
-    pub comments: Vec<(String, String)>, // (author, comment_body)
-    pub comments_count: usize,           // Added to track the number of comments
+use octocrab::{FromResponse, Octocrab, Page};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+impl From<octocrab::models::issues::Issue> for Issue {
+    /// Converts an API issue without its comments, which need a separate request.
+    fn from(issue: octocrab::models::issues::Issue) -> Self {
+        Self {
+            number: issue.number,
+            title: issue.title,
+            author: issue.user.login,
+            body: issue.body.unwrap_or_default(),
+            state: match issue.state {
+                IssueState::Open => "open".to_string(),
+                IssueState::Closed => "closed".to_string(),
+                _ => "unknown".to_string(),
+            },
+            created_at: issue.created_at,
+            updated_at: issue.updated_at,
+            closed_at: issue.closed_at,
+            labels: issue.labels.iter().map(|l| l.name.clone()).collect(),
+            comments: vec![],
+            comments_count: issue.comments as usize,
+        }
+    }
+}
+
+impl From<octocrab::models::pulls::PullRequest> for PullRequest {
+    fn from(pr: octocrab::models::pulls::PullRequest) -> Self {
+        let body = pr.body.unwrap_or_default();
+        let title = pr.title.unwrap_or_default();
+        let state = if pr.merged_at.is_some() {
+            "merged".to_string()
+        } else {
+            match pr.state {
+                Some(IssueState::Open) => "open".to_string(),
+                Some(IssueState::Closed) => "closed".to_string(),
+                _ => "unknown".to_string(),
+            }
+        };
+        Self {
+            number: pr.number,
+            linked_issues: linked_issues(&format!("{}\n{}", title, body)),
+            title,
+            author: pr.user.map(|u| u.login).unwrap_or_default(),
+            body,
+            state,
+            head_branch: pr.head.ref_field,
+            base_branch: pr.base.ref_field,
+            updated_at: pr.updated_at.unwrap_or_default(),
+            merged_at: pr.merged_at,
+        }
+    }
+}
+
+impl From<octocrab::models::repos::Release> for Release {
+    fn from(release: octocrab::models::repos::Release) -> Self {
+        let body = release.body.unwrap_or_default();
+        Self {
+            id: release.id.0,
+            name: release.name.unwrap_or_else(|| release.tag_name.clone()),
+            tag_name: release.tag_name,
+            author: release.author.map(|a| a.login).unwrap_or_default(),
+            linked_issues: linked_issues(&body),
+            body,
+            prerelease: release.prerelease,
+        }
+    }
+}
+
+impl From<octocrab::models::issues::Comment> for Comment {
+    fn from(comment: octocrab::models::issues::Comment) -> Self {
+        Self {
+            id: comment.id.into_inner(),
+            author: comment.user.login,
+            body: comment.body.unwrap_or_default(),
+        }
+    }
+}
+
+/// Below this share of the quota, polling slows down to last until the reset.
+const LOW_QUOTA_PERCENT: u64 = 10;
+/// Rough number of requests a poll costs: issues, pull requests and releases.
+const REQUESTS_PER_POLL: u64 = 3;
+
+/// REST API quota, read from the `X-RateLimit-*` headers of a response.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RateLimit {
+    pub resource: String,
+    pub limit: u64,
+    pub remaining: u64,
+    pub used: u64,
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| header(headers, name)?.parse::<u64>().ok();
+        Some(Self {
+            resource: header(headers, "x-ratelimit-resource")
+                .unwrap_or("core")
+                .to_string(),
+            limit: number("x-ratelimit-limit")?,
+            remaining: number("x-ratelimit-remaining")?,
+            used: number("x-ratelimit-used").unwrap_or_default(),
+            reset: DateTime::from_timestamp(number("x-ratelimit-reset")? as i64, 0)?,
+        })
+    }
+
+    /// Stretches the polling `interval` once quota runs low, so that the
+    /// remaining requests last until the reset.
+    pub fn poll_interval(&self, interval: Duration, now: DateTime<Utc>) -> Duration {
+        let until_reset = (self.reset - now).to_std().unwrap_or_default();
+        if self.remaining == 0 {
+            return interval.max(until_reset);
+        }
+        if self.remaining * 100 > self.limit * LOW_QUOTA_PERCENT {
+            return interval;
+        }
+        let polls_left = (self.remaining / REQUESTS_PER_POLL).max(1);
+        interval.max(until_reset / polls_left as u32)
+    }
 }
 
-impl Display for Issue {
-    /// Formats the issue for adding to the context prompt.
-    /// We add all the relevant fields to the prompt.
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let formatted_comments: Vec<String> = self
-            .comments
-            .iter()
-            .map(|(author, body)| format!("  - {}: {}", author, body))
-            .collect();
-
-        write!(
-            f,
-            "Issue #{}: {}\nState: {}\nUpdated at: {}\nLabels: {:?}\nBody: {}\nComments and Updates:\n{}",
-            self.number,
-            self.title,
-            self.state,
-            self.updated_at.to_rfc3339(),
-            self.labels,
-            self.body,
-            formatted_comments.join("\n")
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok()
+}
+
+impl From<octocrab::Error> for ForgeCallError {
+    fn from(error: octocrab::Error) -> Self {
+        match error {
+            octocrab::Error::GitHub { source, .. } => Self::classify(
+                source.status_code,
+                format!("{}: {}", source.status_code, source.message),
+            ),
+            error => {
+                let kind = match error {
+                    octocrab::Error::Hyper { .. } | octocrab::Error::Service { .. } => {
+                        ForgeErrorKind::Transient
+                    }
+                    // Octocrab's own retry layer has already retried 5xx answers
+                    // whose body fails to decode, so a decoding error is permanent
+                    _ => ForgeErrorKind::Other,
+                };
+                // The rest of the message is a backtrace
+                let message = error.to_string();
+                Self::new(kind, message.lines().next().unwrap_or_default().to_string())
+            }
+        }
+    }
+}
+
+/// Installation tokens live for an hour and are renewed this long before they
+/// expire, so a clone or fetch never starts with a token about to lapse.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Credentials for the API and for git over HTTPS.
+enum Auth {
+    PersonalToken(String),
+    App(Box<AppInstallation>),
+}
+
+/// A GitHub App installation. Its short-lived access tokens are minted with a
+/// JWT signed by the App's private key.
+struct AppInstallation {
+    /// Authenticated as the App itself, with a JWT.
+    app: Octocrab,
+    installation_id: InstallationId,
+    /// Current installation token and its expiry.
+    token: tokio::sync::Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+impl AppInstallation {
+    /// Returns the cached installation token, minting a new one when it is
+    /// missing or close to expiry.
+    async fn token(&self) -> Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some((token, expires_at)) = cached.as_ref()
+            && !needs_refresh(*expires_at, Utc::now())
+        {
+            return Ok(token.clone());
+        }
+
+        let route = format!("/app/installations/{}/access_tokens", self.installation_id);
+        let minted = with_retries(
+            "Failed to create an installation access token",
+            true,
+            || {
+                let route = &route;
+                async move { self.app.post::<(), InstallationToken>(route, None).await }
+            },
         )
+        .await?;
+        let expires_at = minted
+            .expires_at
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(1));
+        // The previous token lapses within minutes, so it no longer needs masking
+        redact::replace(
+            cached.as_ref().map(|(token, _)| token.as_str()),
+            &minted.token,
+        );
+        println!(
+            "Installation access token refreshed, valid until {}",
+            expires_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        *cached = Some((minted.token.clone(), expires_at));
+        Ok(minted.token)
     }
 }
 
+/// Whether a token expiring at `expires_at` should be replaced at `now`.
+fn needs_refresh(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    (expires_at - now)
+        .to_std()
+        .ok()
+        .is_none_or(|left| left < TOKEN_REFRESH_MARGIN)
+}
+
 pub(crate) struct GitHubClient {
     pub octocrab: Octocrab,
     repo_owner: String,
     repo_name: String,
-    access_token: String,
+    auth: Auth,
+    /// Overrides the GitHub clone URL, see `Config::clone_url`.
+    clone_url: Option<String>,
+    rate_limit: Mutex<Option<RateLimit>>,
+    monitor: Option<Arc<Monitor>>,
 }
 
 impl GitHubClient {
+    /// Authenticates as the configured GitHub App installation, or else with
+    /// the personal access token.
     pub(crate) async fn new(config: &Config) -> Result<Self> {
-        let octocrab_with_token = Octocrab::builder()
-            .personal_token(config.github_personal_access_token.clone())
-            .build()
-            .map_err(|e| anyhow::anyhow!("Failed to create Octocrab client with token: {}", e))?;
-
-        println!("Octocrab client with personal access token created successfully.");
+        let (octocrab, auth) = match (&config.github_app, &config.github_personal_access_token) {
+            (Some(app), _) => {
+                let pem = std::fs::read(&app.private_key_path).context(format!(
+                    "Failed to read GitHub App private key {}",
+                    app.private_key_path
+                ))?;
+                redact::register(&String::from_utf8_lossy(&pem));
+                let key = jsonwebtoken::EncodingKey::from_rsa_pem(&pem)
+                    .map_err(|e| anyhow::anyhow!("Invalid GitHub App private key: {}", e))?;
+                let app_client = Octocrab::builder()
+                    .app(AppId(app.app_id), key)
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("Failed to create GitHub App client: {}", e))?;
+                let installation_id = InstallationId(app.installation_id);
+                // Octocrab renews the API's installation token by itself
+                let octocrab = app_client
+                    .installation(installation_id)
+                    .map_err(|e| anyhow::anyhow!("Failed to create installation client: {}", e))?;
+                println!(
+                    "Octocrab client for GitHub App {} installation {} created successfully.",
+                    app.app_id, app.installation_id
+                );
+                let installation = AppInstallation {
+                    app: app_client,
+                    installation_id,
+                    token: tokio::sync::Mutex::new(None),
+                };
+                (octocrab, Auth::App(Box::new(installation)))
+            }
+            (None, Some(token)) => {
+                let octocrab = Octocrab::builder()
+                    .personal_token(token.clone())
+                    .build()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to create Octocrab client with token: {}", e)
+                    })?;
+                println!("Octocrab client with personal access token created successfully.");
+                (octocrab, Auth::PersonalToken(token.clone()))
+            }
+            (None, None) => {
+                return Err(anyhow::anyhow!("No GitHub credentials configured"));
+            }
+        };
 
         Ok(Self {
-            octocrab: octocrab_with_token,
+            octocrab,
             repo_owner: config.github_repository_owner.clone(),
             repo_name: config.github_repository_name.clone(),
-            access_token: config.github_personal_access_token.clone(),
+            auth,
+            clone_url: config.clone_url.clone(),
+            rate_limit: Mutex::new(None),
+            monitor: None,
         })
     }
 
-    pub(crate) async fn clone_repository(&self) -> Result<(TempDir, Repository)> {
-        let token = self.access_token.clone();
-        let clone_url = format!(
-            "https://x-access-token:{}@github.com/{}/{}.git",
-            token, self.repo_owner, self.repo_name
-        );
-        println!("Clone URL: {}", clone_url);
-
-        let repo_dir = TempDir::new()
-            .map_err(|e| anyhow::anyhow!("Failed to create temporary directory for repo: {}", e))?;
-
-        println!(
-            "Temporary repository directory created at: {}",
-            repo_dir.path().display()
-        );
+    /// Token for git over HTTPS, renewed before expiry for a GitHub App.
+    async fn access_token(&self) -> Result<String> {
+        match &self.auth {
+            Auth::PersonalToken(token) => Ok(token.clone()),
+            Auth::App(installation) => installation.token().await,
+        }
+    }
 
-        let repo = Repository::clone(&clone_url, repo_dir.path())
-            .map_err(|e| anyhow::anyhow!("Failed to clone repository: {}", e))?;
+    /// Remembers the quota reported by a response and shows it in the dashboard.
+    fn record_rate_limit(&self, headers: &HeaderMap) {
+        let Some(rate_limit) = RateLimit::from_headers(headers) else {
+            return;
+        };
+        if let Some(monitor) = &self.monitor {
+            monitor.set_rate_limit(rate_limit.clone());
+        }
+        *self.rate_limit.lock().unwrap_or_else(|e| e.into_inner()) = Some(rate_limit);
+    }
+}
 
-        println!(
-            "Repository cloned successfully into {}",
-            repo_dir.path().display()
-        );
-        Ok((repo_dir, repo))
+#[async_trait]
+impl Forge for GitHubClient {
+    /// URL to clone and fetch from, carrying a currently valid token.
+    async fn clone_url(&self) -> Result<String> {
+        if let Some(url) = &self.clone_url {
+            return Ok(url.clone());
+        }
+        Ok(format!(
+            "https://x-access-token:{}@github.com/{}/{}.git",
+            self.access_token().await?,
+            self.repo_owner,
+            self.repo_name
+        ))
     }
 
-    pub async fn get_issue_comments(&self, issue_number: u64) -> Result<Vec<Comment>> {
+    async fn get_issue_comments(&self, issue_number: u64) -> Result<Vec<Comment>> {
         let mut all_comments = Vec::new();
         let mut page = 1u32;
 
         loop {
-            println!("Fetching comments for issue #{} on page {}", issue_number, page);
+            println!(
+                "Fetching comments for issue #{} on page {}",
+                issue_number, page
+            );
             let current_page = page;
-            let comments_page = self.
-                    octocrab
+            let comments_page = with_retries(
+                &format!("Failed to list comments for issue #{}", issue_number),
+                true,
+                || async move {
+                    self.octocrab
                         .issues(&self.repo_owner, &self.repo_name)
                         .list_comments(issue_number)
                         .per_page(100)
                         .page(current_page)
                         .send()
                         .await
-                        .context(format!(
-                            "Failed to list comments for issue #{}",
-                            issue_number
-                        ))?;
+                },
+            )
+            .await?;
 
             if comments_page.items.is_empty() {
                 break;
             }
 
-            all_comments.extend(comments_page.items);
+            all_comments.extend(comments_page.items.into_iter().map(Comment::from));
 
             if comments_page.next.is_none() {
                 break;
@@ -130,291 +378,532 @@ impl GitHubClient {
         Ok(all_comments)
     }
 
-    pub async fn list_all_issues(&self, state: Option<String>) -> Result<Vec<Issue>> {
-        let mut all_issues = Vec::new();
-
-        match state.as_deref() {
-            Some("open") => {
-                let mut page = 1u32;
-                loop {
-                    let current_page = page;
-                    let issue_page = self.octocrab
-                                .issues(&self.repo_owner, &self.repo_name)
-                                .list()
-                                .state(octocrab::params::State::Open)
-                                .sort(Sort::Created)
-                                .direction(Direction::Descending)
-                                .per_page(100)
-                                .page(current_page)
-                                .send()
-                                .await
-                                .context(format!(
-                                    "Failed to list open issues (page {})",
-                                    current_page
-                                ))?;
-
-                    if issue_page.items.is_empty() {
-                        break;
-                    }
-
-                    all_issues.extend(issue_page.items);
+    /// Lists the issues updated since `since`, or all of them. When `etag`
+    /// still matches, GitHub answers 304 without using quota and `issues` is `None`.
+    async fn list_issues_updated_since(
+        &self,
+        since: Option<DateTime<Utc>>,
+        etag: Option<&str>,
+    ) -> Result<IssueUpdates> {
+        let mut uri = format!(
+            "/repos/{}/{}/issues?state=all&sort=updated&direction=asc&per_page=100",
+            self.repo_owner, self.repo_name
+        );
+        if let Some(since) = since {
+            uri.push_str(&format!(
+                "&since={}",
+                since.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ));
+        }
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = etag {
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+        }
 
-                    if issue_page.next.is_none() {
-                        break;
-                    }
-                    page += 1;
+        let mut items = Vec::new();
+        let mut new_etag = None;
+        let mut headers = Some(headers);
+        let mut next = Some(uri);
+        while let Some(uri) = next.take() {
+            let request_headers = headers.take();
+            let request_headers = &request_headers;
+            let uri = &uri;
+            let response = with_retries("Failed to list updated issues", true, || async move {
+                let response = self
+                    .octocrab
+                    ._get_with_headers(uri.as_str(), request_headers.clone())
+                    .await?;
+                self.record_rate_limit(response.headers());
+                let status = response.status();
+                if status == StatusCode::NOT_MODIFIED {
+                    return Ok(response);
                 }
-            }
-            Some("closed") => {
-                let mut page = 1u32;
-                loop {
-                    let current_page = page;
-                    let issue_page = self.octocrab
-                                .issues(&self.repo_owner, &self.repo_name)
-                                .list()
-                                .state(octocrab::params::State::Closed)
-                                .sort(Updated)
-                                .direction(Direction::Descending)
-                                .per_page(100)
-                                .page(current_page)
-                                .send()
-                                .await
-                                .context(format!(
-                                    "Failed to list closed issues (page {})",
-                                    current_page
-                                ))?;
-
-                    if issue_page.items.is_empty() {
-                        break;
-                    }
-
-                    all_issues.extend(issue_page.items);
-
-                    if issue_page.next.is_none() {
-                        break;
-                    }
-                    page += 1;
+                let headers = response.headers().clone();
+                if status.is_server_error() {
+                    // The body of a 5xx is often not JSON
+                    return Err(ForgeCallError::from_status(status).with_retry_after(&headers));
                 }
+                octocrab::map_github_error(response)
+                    .await
+                    .map_err(|e| ForgeCallError::from(e).with_retry_after(&headers))
+            })
+            .await?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(IssueUpdates {
+                    issues: None,
+                    etag: etag.map(str::to_string),
+                    last_updated: since,
+                });
             }
-            Some("all") | None => {
-                // Fetch open issues
-                let mut page = 1u32;
-                loop {
-                    let current_page = page;
-                    let issue_page = self.octocrab
-                                .issues(&self.repo_owner, &self.repo_name)
-                                .list()
-                                .state(octocrab::params::State::Open)
-                                .sort(Updated)
-                                .direction(Direction::Descending)
-                                .per_page(100)
-                                .page(current_page)
-                                .send()
-                                .await
-                                .context(format!(
-                                    "Failed to list open issues (page {})",
-                                    current_page
-                                ))?;
-
-                    if issue_page.items.is_empty() {
-                        break;
-                    }
-
-                    all_issues.extend(issue_page.items);
-
-                    if issue_page.next.is_none() {
-                        break;
-                    }
-                    page += 1;
-                }
-
-                // Fetch closed issues
-                page = 1;
-                loop {
-                    let current_page = page;
-                    let issue_page = self.octocrab
-                                .issues(&self.repo_owner, &self.repo_name)
-                                .list()
-                                .state(octocrab::params::State::Closed)
-                                .sort(Updated)
-                                .direction(Direction::Descending)
-                                .per_page(100)
-                                .page(current_page)
-                                .send()
-                                .await
-                                .context(format!(
-                                    "Failed to list closed issues (page {})",
-                                    current_page
-                                ))?;
-
-                    if issue_page.items.is_empty() {
-                        break;
-                    }
-
-                    all_issues.extend(issue_page.items);
-
-                    if issue_page.next.is_none() {
-                        break;
-                    }
-                    page += 1;
-                }
+            if new_etag.is_none() {
+                new_etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
             }
-            _ => return Err(anyhow::anyhow!("Invalid state parameter")),
+            let page = Page::<octocrab::models::issues::Issue>::from_response(response)
+                .await
+                .context("Failed to read updated issues")?;
+            items.extend(page.items);
+            next = page.next.map(|uri| uri.to_string());
         }
 
-        println!("Fetched {} items (issues and pull requests)", all_issues.len());
-        // Filter out pull requests and convert octocrab::models::Issue to our Issue struct
-        let filtered_issues = all_issues
+        // Pull requests count too, or they would be listed again on every poll
+        let last_updated = items.iter().map(|item| item.updated_at).max().or(since);
+        let issues = items
             .into_iter()
-            .filter(|item| item.pull_request.is_none()) // Filter out pull requests
-            .map(|item| Issue {
-                number: item.number,
-                title: item.title,
-                body: item.body.unwrap_or_default(),
-                state: match item.state {
-                    IssueState::Open => "open".to_string(),
-                    IssueState::Closed => "closed".to_string(),
-                    _ => "unknown".to_string(),
-                },
-                updated_at: item.updated_at,
-                labels: item.labels.iter().map(|l| l.name.clone()).collect(),
-                comments: vec![],
-                comments_count: item.comments as usize,
-            })
+            .filter(|item| item.pull_request.is_none())
+            .map(Issue::from)
             .collect::<Vec<Issue>>();
+        println!("Fetched {} updated issues", issues.len());
 
-        println!("Parsed {} issues from the response.", filtered_issues.len());
+        Ok(IssueUpdates {
+            issues: Some(issues),
+            etag: new_etag,
+            last_updated,
+        })
+    }
 
-        Ok(filtered_issues)
+    /// The quota reported by the last conditional listing, if any.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
-    pub(crate) async fn get_issue(&self, issue_number: u64) -> anyhow::Result<Issue> {
-        let issue = self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .get(issue_number)
-                .await
-                .context(format!("Failed to get issue #{}", issue_number))?;
-            let comments = self.get_issue_comments(issue_number).await?;
-            let comments_and_updates = comments
-                .iter()
-                .map(|c| (c.user.login.clone(), c.body.clone().unwrap_or_default()))
-                .collect::<Vec<(String, String)>>();
-
-            Ok(Issue {
-                number: issue.number,
-                title: issue.title,
-                body: issue.body.unwrap_or_default(),
-                state: match issue.state {
-                    IssueState::Open => "open".to_string(),
-                    IssueState::Closed => "closed".to_string(),
-                    _ => "unknown".to_string(),
-                },
-                updated_at: issue.updated_at,
-                labels: issue.labels.iter().map(|l| l.name.clone()).collect(),
-                comments: comments_and_updates,
-                comments_count: issue.comments as usize,
-            })
+    fn set_monitor(&mut self, monitor: Arc<Monitor>) {
+        self.monitor = Some(monitor);
     }
 
-    pub(crate) async fn create_issue(
-        &self,
-        title: String,
-        body: String,
-        labels: Vec<String>,
-    ) -> Result<u64> {
-        let title_clone = title.clone();
-        let body_clone = body.clone();
-        let labels_clone = labels.clone();
-
-            let i = self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .create(title_clone.clone())
-                .body(body_clone.clone())
-                .labels(labels_clone.clone())
-                .send()
-                .await
-                .context("Failed to create new issue")?;
+    async fn get_issue(&self, issue_number: u64) -> anyhow::Result<Issue> {
+        let issue = with_retries(
+            &format!("Failed to get issue #{}", issue_number),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .get(issue_number)
+                    .await
+            },
+        )
+        .await?;
+        let comments = self.get_issue_comments(issue_number).await?;
+        let comments_and_updates = comments
+            .into_iter()
+            .map(|c| (c.author, c.body))
+            .collect::<Vec<(String, String)>>();
 
-            Ok(i.number)
+        Ok(Issue {
+            comments: comments_and_updates,
+            ..Issue::from(issue)
+        })
     }
 
-    pub(crate) async fn add_label_to_issue(
-        &self,
-        issue_number: u64,
-        label: &str,
-    ) -> Result<Vec<octocrab::models::Label>> {
-        let label_vec = vec![label.to_string()];
+    async fn create_issue(&self, title: String, body: String, labels: Vec<String>) -> Result<u64> {
+        let (title, body, labels) = (&title, &body, &labels);
+        // Not idempotent, a retried request could open the issue twice
+        let i = with_retries("Failed to create new issue", false, || async move {
             self.octocrab
                 .issues(&self.repo_owner, &self.repo_name)
-                .add_labels(issue_number, &label_vec)
+                .create(title.clone())
+                .body(body.clone())
+                .labels(labels.clone())
+                .send()
                 .await
-                .context(format!("Failed to add label to issue #{}", issue_number))
+        })
+        .await?;
+
+        Ok(i.number)
     }
 
-    pub(crate) async fn remove_label_from_issue(
-        &self,
-        issue_number: u64,
-        label: &str,
-    ) -> Result<Vec<octocrab::models::Label>> {
-        let label_str = label.to_string();
+    async fn add_label_to_issue(&self, issue_number: u64, label: &str) -> Result<()> {
+        let labels = &[label.to_string()];
+        with_retries(
+            &format!("Failed to add label to issue #{}", issue_number),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .add_labels(issue_number, labels)
+                    .await
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_label_from_issue(&self, issue_number: u64, label: &str) -> Result<()> {
+        with_retries(
+            &format!(
+                "Failed to remove label '{}' from issue #{}",
+                label, issue_number
+            ),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .remove_label(issue_number, label)
+                    .await
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn close_issue(&self, issue_number: u64) -> Result<()> {
+        with_retries(
+            &format!("Failed to close issue #{}", issue_number),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .update(issue_number)
+                    .state(IssueState::Closed)
+                    .send()
+                    .await
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn comment_on_issue(&self, issue_number: u64, body: &str) -> Result<()> {
+        // Not idempotent, a retried request could post the comment twice
+        with_retries(
+            &format!("Failed to comment on issue #{}", issue_number),
+            false,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .create_comment(issue_number, body)
+                    .await
+            },
+        )
+        .await?;
+        println!("Commented on issue #{}", issue_number);
+        Ok(())
+    }
+
+    async fn edit_issue_body(&self, issue_number: u64, body: &str) -> Result<()> {
+        with_retries(
+            &format!("Failed to edit body of issue #{}", issue_number),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .update(issue_number)
+                    .body(body)
+                    .send()
+                    .await
+            },
+        )
+        .await?;
+        println!("Edited body of issue #{}", issue_number);
+        Ok(())
+    }
+
+    async fn edit_issue_title(&self, issue_number: u64, title: &str) -> Result<()> {
+        with_retries(
+            &format!("Failed to edit title of issue #{}", issue_number),
+            true,
+            || async move {
+                self.octocrab
+                    .issues(&self.repo_owner, &self.repo_name)
+                    .update(issue_number)
+                    .title(title)
+                    .send()
+                    .await
+            },
+        )
+        .await?;
+        println!("Edited title of issue #{}", issue_number);
+        Ok(())
+    }
+
+    /// Lists pull requests in the given state: "open", "closed", "merged" or "all".
+    async fn list_pull_requests(&self, state: Option<String>) -> Result<Vec<PullRequest>> {
+        let (api_state, merged_only) = match state.as_deref() {
+            Some("open") => (octocrab::params::State::Open, false),
+            Some("closed") => (octocrab::params::State::Closed, false),
+            Some("merged") => (octocrab::params::State::Closed, true),
+            Some("all") | None => (octocrab::params::State::All, false),
+            _ => return Err(anyhow::anyhow!("Invalid state parameter")),
+        };
+
+        let mut all_pull_requests = Vec::new();
+        let mut page = 1u32;
+        loop {
+            let current_page = page;
+            let pr_page = with_retries(
+                &format!("Failed to list pull requests (page {})", current_page),
+                true,
+                || async move {
+                    self.octocrab
+                        .pulls(&self.repo_owner, &self.repo_name)
+                        .list()
+                        .state(api_state)
+                        .sort(octocrab::params::pulls::Sort::Updated)
+                        .direction(Direction::Descending)
+                        .per_page(100)
+                        .page(current_page)
+                        .send()
+                        .await
+                },
+            )
+            .await?;
+
+            if pr_page.items.is_empty() {
+                break;
+            }
+
+            all_pull_requests.extend(pr_page.items.into_iter().map(PullRequest::from));
+
+            if pr_page.next.is_none() {
+                break;
+            }
+            page += 1;
+        }
+
+        if merged_only {
+            all_pull_requests.retain(|pr| pr.merged_at.is_some());
+        }
+        println!("Fetched {} pull requests", all_pull_requests.len());
+        Ok(all_pull_requests)
+    }
+
+    /// Lists the most recently updated pull requests, in any state.
+    /// One page is enough to catch everything that changed since the previous poll.
+    async fn list_recent_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        let pr_page = with_retries("Failed to list recent pull requests", true, || async move {
             self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .remove_label(issue_number, &label_str)
+                .pulls(&self.repo_owner, &self.repo_name)
+                .list()
+                .state(octocrab::params::State::All)
+                .sort(octocrab::params::pulls::Sort::Updated)
+                .direction(Direction::Descending)
+                .per_page(100)
+                .send()
                 .await
-                .context(format!(
-                    "Failed to remove label '{}' from issue #{}",
-                    label_str, issue_number
-                ))
+        })
+        .await?;
+        Ok(pr_page.items.into_iter().map(PullRequest::from).collect())
     }
 
-    pub(crate) async fn close_issue(&self, issue_number: u64) -> Result<()> {
+    /// Lists the most recent published releases, drafts excluded.
+    async fn list_recent_releases(&self) -> Result<Vec<Release>> {
+        let releases = with_retries("Failed to list releases", true, || async move {
             self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .update(issue_number)
-                .state(IssueState::Closed)
+                .repos(&self.repo_owner, &self.repo_name)
+                .releases()
+                .list()
+                .per_page(30)
                 .send()
                 .await
-                .context(format!("Failed to close issue #{}", issue_number))?;
+        })
+        .await?;
+        Ok(releases
+            .items
+            .into_iter()
+            .filter(|release| !release.draft)
+            .map(Release::from)
+            .collect())
+    }
 
-            Ok(())
+    async fn get_pull_request(&self, pull_request_number: u64) -> Result<PullRequest> {
+        let pr = with_retries(
+            &format!("Failed to get pull request #{}", pull_request_number),
+            true,
+            || async move {
+                self.octocrab
+                    .pulls(&self.repo_owner, &self.repo_name)
+                    .get(pull_request_number)
+                    .await
+            },
+        )
+        .await?;
+        Ok(PullRequest::from(pr))
     }
 
-    pub(crate) async fn comment_on_issue(&self, issue_number: u64, body: &str) -> Result<Comment> {
-        let body_str = body.to_string();
+    async fn get_pull_request_files(
+        &self,
+        pull_request_number: u64,
+    ) -> Result<Vec<PullRequestFile>> {
+        let what = format!(
+            "Failed to list files of pull request #{}",
+            pull_request_number
+        );
+        let first_page = &with_retries(&what, true, || async move {
             self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .create_comment(issue_number, &body_str)
+                .pulls(&self.repo_owner, &self.repo_name)
+                .list_files(pull_request_number)
                 .await
-                .context(format!("Failed to comment on issue #{}", issue_number))
-                .and_then(|comment| {
-                    println!("Commented on issue #{}", issue_number);
-                    Ok(comment)
-                })
+        })
+        .await?;
+        let entries = with_retries(&what, true, || async move {
+            self.octocrab.all_pages(first_page.clone()).await
+        })
+        .await?;
+
+        Ok(entries
+            .into_iter()
+            .map(|entry| PullRequestFile {
+                filename: entry.filename,
+                status: serde_json::to_value(&entry.status)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                additions: entry.additions,
+                deletions: entry.deletions,
+            })
+            .collect())
     }
 
-    pub(crate) async fn edit_issue_body(&self, issue_number: u64, body: &str) -> Result<()> {
-        let body_str = body.to_string();
-            self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .update(issue_number)
-                .body(&body_str)
-                .send()
-                .await
-                .context(format!("Failed to edit body of issue #{}", issue_number))?;
-            println!("Edited body of issue #{}", issue_number);
-            Ok(())
+    async fn get_pull_request_diff(&self, pull_request_number: u64) -> Result<String> {
+        with_retries(
+            &format!(
+                "Failed to get diff of pull request #{}",
+                pull_request_number
+            ),
+            true,
+            || async move {
+                self.octocrab
+                    .pulls(&self.repo_owner, &self.repo_name)
+                    .get_diff(pull_request_number)
+                    .await
+            },
+        )
+        .await
     }
 
-    pub(crate) async fn edit_issue_title(&self, issue_number: u64, title: &str) -> Result<()> {
-        let title_str = title.to_string();
+    async fn list_pull_request_review_comments(
+        &self,
+        pull_request_number: u64,
+    ) -> Result<Vec<ReviewComment>> {
+        let what = format!(
+            "Failed to list review comments of pull request #{}",
+            pull_request_number
+        );
+        let first_page = &with_retries(&what, true, || async move {
             self.octocrab
-                .issues(&self.repo_owner, &self.repo_name)
-                .update(issue_number)
-                .title(&title_str)
+                .pulls(&self.repo_owner, &self.repo_name)
+                .list_comments(Some(pull_request_number))
+                .per_page(100)
                 .send()
                 .await
-                .context(format!("Failed to edit title of issue #{}", issue_number))?;
-            println!("Edited title of issue #{}", issue_number);
-            Ok(())
+        })
+        .await?;
+        let comments = with_retries(&what, true, || async move {
+            self.octocrab.all_pages(first_page.clone()).await
+        })
+        .await?;
+
+        Ok(comments
+            .into_iter()
+            .map(|c| ReviewComment {
+                author: c.user.map(|u| u.login).unwrap_or_default(),
+                path: c.path,
+                line: c.line.or(c.original_line),
+                body: c.body,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Starts a GitHub stand-in answering every request with `status` and
+    /// `body`, and returns a client pointed at it with the number of requests.
+    async fn mock_server(status: u16, body: &'static str) -> (GitHubClient, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_uri = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = [0; 4096];
+                let _ = socket.read(&mut buffer).await;
+                counted.fetch_add(1, Ordering::SeqCst);
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let octocrab = Octocrab::builder()
+            .base_uri(base_uri)
+            .unwrap()
+            .personal_token("ghp_test_token".to_string())
+            .build()
+            .unwrap();
+        let client = GitHubClient {
+            octocrab,
+            repo_owner: "octo".to_string(),
+            repo_name: "repo".to_string(),
+            auth: Auth::PersonalToken("ghp_test_token".to_string()),
+            clone_url: None,
+            rate_limit: Mutex::new(None),
+            monitor: None,
+        };
+        (client, requests)
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_success_is_not_retried() {
+        let (client, requests) = mock_server(200, r#"{"number": "one"}"#).await;
+        let error = client.get_issue(1).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ForgeCallError>().unwrap().kind,
+            ForgeErrorKind::Other
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("5000"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("4000"));
+        headers.insert("x-ratelimit-used", HeaderValue::from_static("1000"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1700003600"));
+        let mut rate_limit = RateLimit::from_headers(&headers).unwrap();
+        assert_eq!(rate_limit.resource, "core");
+        assert_eq!(rate_limit.remaining, 4000);
+
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let interval = Duration::from_secs(5);
+        assert_eq!(rate_limit.poll_interval(interval, now), interval);
+        // 300 requests are 100 polls over the hour left
+        rate_limit.remaining = 300;
+        assert_eq!(
+            rate_limit.poll_interval(interval, now),
+            Duration::from_secs(36)
+        );
+        rate_limit.remaining = 0;
+        assert_eq!(
+            rate_limit.poll_interval(interval, now),
+            Duration::from_secs(3600)
+        );
+
+        headers.remove("x-ratelimit-remaining");
+        assert!(RateLimit::from_headers(&headers).is_none());
+    }
+
+    #[test]
+    fn test_needs_refresh() {
+        let now = Utc::now();
+        assert!(needs_refresh(now - chrono::Duration::minutes(1), now));
+        assert!(needs_refresh(now + chrono::Duration::minutes(4), now));
+        assert!(!needs_refresh(now + chrono::Duration::minutes(30), now));
     }
 }
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::forge::Issue;
+
+/// Version of the on-disk issue cache. The cache can always be rebuilt from
+/// GitHub, so a file with another version is discarded instead of migrated.
+pub const ISSUE_STORE_VERSION: u32 = 1;
+
+const ISSUE_STORE_FILE_NAME: &str = "issues.json";
+
+/// Local copy of the repository's issues keyed by number, kept in sync with
+/// `since` listings and persisted in the data directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssueStore {
+    version: u32,
+    issues: BTreeMap<u64, Issue>,
+    /// Latest `updated_at` seen, the `since` of the next listing.
+    last_sync: Option<DateTime<Utc>>,
+    /// ETag of the last listing, so an unchanged repository costs no quota.
+    etag: Option<String>,
+    /// Highest comment ID seen per issue, used to report only new comments.
+    comment_high_water: BTreeMap<u64, u64>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl IssueStore {
+    /// Loads the store from the data directory. A missing, unreadable or
+    /// outdated file yields an empty store, filled by the next full listing.
+    pub fn open(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let data_dir = data_dir.as_ref();
+        fs::create_dir_all(data_dir).context(format!(
+            "Failed to create data directory {}",
+            data_dir.display()
+        ))?;
+        let path = data_dir.join(ISSUE_STORE_FILE_NAME);
+        let empty = Self {
+            version: ISSUE_STORE_VERSION,
+            issues: BTreeMap::new(),
+            last_sync: None,
+            etag: None,
+            comment_high_water: BTreeMap::new(),
+            path: path.clone(),
+            changed: false,
+        };
+        if !path.exists() {
+            return Ok(empty);
+        }
+
+        let loaded = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(serde_json::from_str::<Self>(&content)?));
+        match loaded {
+            Ok(store) if store.version == ISSUE_STORE_VERSION => Ok(Self { path, ..store }),
+            Ok(store) => {
+                println!(
+                    "Discarding issue cache {} with version {}",
+                    path.display(),
+                    store.version
+                );
+                Ok(empty)
+            }
+            Err(e) => {
+                println!(
+                    "Discarding unreadable issue cache {}: {}",
+                    path.display(),
+                    e
+                );
+                Ok(empty)
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the store atomically if it changed since it was loaded or last saved.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize issue cache")?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).context(format!(
+            "Failed to write issue cache {}",
+            tmp_path.display()
+        ))?;
+        fs::rename(&tmp_path, &self.path).context(format!(
+            "Failed to replace issue cache {}",
+            self.path.display()
+        ))?;
+        self.changed = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.issues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn get(&self, number: u64) -> Option<&Issue> {
+        self.issues.get(&number)
+    }
+
+    /// Issues in ascending number order.
+    pub fn issues(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.values()
+    }
+
+    pub fn highest_number(&self) -> Option<u64> {
+        self.issues.keys().next_back().copied()
+    }
+
+    pub fn last_sync(&self) -> Option<DateTime<Utc>> {
+        self.last_sync
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Records where the next incremental listing starts.
+    pub fn set_sync(&mut self, last_sync: Option<DateTime<Utc>>, etag: Option<String>) {
+        if self.last_sync != last_sync || self.etag != etag {
+            self.last_sync = last_sync;
+            self.etag = etag;
+            self.changed = true;
+        }
+    }
+
+    /// Stores an updated issue and returns the previous version. Listings do not
+    /// include comments, so the ones already stored are kept.
+    pub fn insert(&mut self, mut issue: Issue) -> Option<Issue> {
+        let previous = self.issues.get(&issue.number).cloned();
+        if let Some(previous) = &previous
+            && issue.comments.is_empty()
+        {
+            issue.comments = previous.comments.clone();
+        }
+        self.issues.insert(issue.number, issue);
+        self.changed = true;
+        previous
+    }
+
+    /// Applies a write the agent made itself, so the store does not serve the
+    /// previous version until the next listing.
+    pub fn update(&mut self, number: u64, change: impl FnOnce(&mut Issue)) {
+        if let Some(issue) = self.issues.get_mut(&number) {
+            change(issue);
+            self.changed = true;
+        }
+    }
+
+    /// Whether all comments of the issue are stored, so it needs no request.
+    pub fn has_all_comments(&self, number: u64) -> bool {
+        self.issues
+            .get(&number)
+            .is_some_and(|issue| issue.comments.len() == issue.comments_count)
+    }
+
+    /// Replaces the stored comments of an issue with freshly fetched ones.
+    pub fn set_comments(&mut self, number: u64, comments: Vec<(String, String)>) {
+        if let Some(issue) = self.issues.get_mut(&number) {
+            issue.comments = comments;
+            self.changed = true;
+        }
+    }
+
+    /// Appends a comment delivered by webhook.
+    pub fn add_comment(&mut self, number: u64, author: String, body: String) {
+        if let Some(issue) = self.issues.get_mut(&number) {
+            // Only a complete list stays complete
+            if issue.comments.len() == issue.comments_count {
+                issue.comments.push((author, body));
+            }
+            issue.comments_count += 1;
+            self.changed = true;
+        }
+    }
+
+    pub fn comment_high_water(&self, number: u64) -> Option<u64> {
+        self.comment_high_water.get(&number).copied()
+    }
+
+    pub fn set_comment_high_water(&mut self, number: u64, comment_id: u64) {
+        if self.comment_high_water.insert(number, comment_id) != Some(comment_id) {
+            self.changed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(number: u64, comments_count: usize) -> Issue {
+        Issue {
+            number,
+            title: format!("Issue {}", number),
+            author: "octocat".to_string(),
+            body: "Body".to_string(),
+            state: "open".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            closed_at: None,
+            labels: vec!["bug".to_string()],
+            comments: vec![],
+            comments_count,
+        }
+    }
+
+    #[test]
+    fn test_save_and_reopen() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut store = IssueStore::open(dir.path()).unwrap();
+        assert!(store.is_empty());
+        store.insert(issue(2, 1));
+        store.insert(issue(7, 0));
+        store.set_comments(2, vec![("ada".to_string(), "Looks good".to_string())]);
+        store.set_sync(Some(Utc::now()), Some("\"abc\"".to_string()));
+        store.set_comment_high_water(2, 41);
+        store.save().unwrap();
+
+        let reopened = IssueStore::open(dir.path()).unwrap();
+        assert_eq!(reopened, store);
+        assert_eq!(reopened.highest_number(), Some(7));
+        assert!(reopened.has_all_comments(2));
+        assert_eq!(reopened.comment_high_water(2), Some(41));
+    }
+
+    #[test]
+    fn test_insert_keeps_comments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut store = IssueStore::open(dir.path()).unwrap();
+        store.insert(issue(1, 1));
+        store.set_comments(1, vec![("ada".to_string(), "First".to_string())]);
+
+        let previous = store.insert(issue(1, 2)).unwrap();
+        assert_eq!(previous.comments_count, 1);
+        assert_eq!(store.get(1).unwrap().comments.len(), 1);
+        assert!(!store.has_all_comments(1));
+
+        store.add_comment(1, "bob".to_string(), "Second".to_string());
+        assert_eq!(store.get(1).unwrap().comments_count, 3);
+        assert_eq!(store.get(1).unwrap().comments.len(), 1);
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut store = IssueStore::open(dir.path()).unwrap();
+        store.insert(issue(1, 0));
+        store.update(1, |issue| issue.title = "Renamed".to_string());
+        store.update(2, |issue| issue.title = "Missing".to_string());
+        assert_eq!(store.get(1).unwrap().title, "Renamed");
+        assert!(store.get(2).is_none());
+
+        // A comment posted by the agent makes the stored list incomplete
+        store.update(1, |issue| issue.comments_count += 1);
+        assert!(!store.has_all_comments(1));
+    }
+
+    #[test]
+    fn test_unreadable_file_is_discarded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join(ISSUE_STORE_FILE_NAME), "not json").unwrap();
+        let store = IssueStore::open(dir.path()).unwrap();
+        assert!(store.is_empty());
+        assert_eq!(store.last_sync(), None);
+    }
+}
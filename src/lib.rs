@@ -1,8 +1,23 @@
+// First, so its `println!` and `eprintln!` replace the standard ones in every module below.
+#[macro_use]
+pub mod redact;
+
 pub mod actions;
 pub mod agent;
+pub mod approval;
 pub mod config;
+pub mod dry_run;
+pub mod forge;
+pub mod gitea;
 pub mod github;
+pub mod issue_store;
 pub mod llm;
 pub mod monitoring;
+pub mod outline;
 pub mod repository;
+pub mod sandbox;
+pub mod schema;
+pub mod search;
+pub mod state;
 pub mod web_server;
+pub mod webhook;
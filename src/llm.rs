@@ -1,17 +1,28 @@
 use crate::config::Config;
 use crate::monitoring::Monitor;
+use crate::redact::redact;
 use anyhow::{Result, anyhow};
 use openai::{
     Credentials,
-    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole},
+    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole, ToolCall},
 };
+use serde_json::{Value, json};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::time::{Duration, sleep};
 
+/// Text and tool calls returned by a completion made with `tools`.
+#[derive(Debug, Clone, Default)]
+pub struct ToolResponse {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+}
+
 pub struct LlmClient {
     model_name: String,
     api_key_present: bool,
     credentials: Credentials,
+    http: reqwest::Client,
     monitor: Option<Arc<Monitor>>,
 }
 
@@ -31,6 +42,7 @@ impl LlmClient {
             api_key_present: !config.openai_api_key.is_empty()
                 || std::env::var("OPENAI_KEY").is_ok(),
             credentials,
+            http: reqwest::Client::new(),
             monitor: None,
         })
     }
@@ -57,14 +69,65 @@ impl LlmClient {
 
         let content = returned_message
             .content
-            .ok_or_else(|| anyhow!("LLM returned empty content"));
+            .ok_or_else(|| anyhow!("LLM returned empty content"))?;
+
+        // Code fences are kept: the model may quote code in its answer.
+        Ok(content.trim().to_string())
+    }
+
+    /// The `openai` crate only supports the legacy `functions` field, so requests
+    /// carrying `tools` are sent directly to the chat completions endpoint.
+    async fn call_llm_with_tools(
+        &self,
+        messages: &[ChatCompletionMessage],
+        tools: &[Value],
+    ) -> Result<ToolResponse> {
+        if !self.api_key_present {
+            return Err(anyhow!(
+                "OpenAI API key not configured. LLM functionality disabled."
+            ));
+        }
+
+        let request = json!({
+            "model": self.model_name,
+            "messages": messages,
+            "tools": tools,
+            "tool_choice": "auto",
+        });
+        let response = self
+            .http
+            .post(format!("{}chat/completions", self.credentials.base_url()))
+            .bearer_auth(self.credentials.api_key())
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to send chat completion request: {}", e))?;
 
-        // Sanitize the content to remove any leading/trailing whitespace
-        let content = content?.trim().to_string();
-        let content = content.replace("```json", "");
-        let content = content.replace("```", ""); // Remove any remaining code block markers
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to read chat completion response: {}", e))?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Chat completion failed with status {}: {}",
+                status,
+                body
+            ));
+        }
 
-        Ok(content)
+        let returned_message: ChatCompletionMessage =
+            serde_json::from_value(body["choices"][0]["message"].clone())
+                .map_err(|e| anyhow!("No message returned from LLM: {}", e))?;
+
+        Ok(ToolResponse {
+            content: returned_message
+                .content
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            tool_calls: returned_message.tool_calls.unwrap_or_default(),
+        })
     }
 
     pub fn set_monitor(&mut self, monitor: Arc<Monitor>) {
@@ -75,66 +138,107 @@ impl LlmClient {
         let start_time = std::time::Instant::now();
         println!("===== USER PROMPT =====\n{}", user_prompt);
 
-        let messages = vec![
-            ChatCompletionMessage {
-                role: ChatCompletionMessageRole::System,
-                content: Some(system_prompt.to_string()),
-                name: None,
-                function_call: None,
-                tool_call_id: None,
-                tool_calls: None,
-            },
-            ChatCompletionMessage {
-                role: ChatCompletionMessageRole::User,
-                content: Some(user_prompt.to_string()),
-                name: None,
-                function_call: None,
-                tool_call_id: None,
-                tool_calls: None,
-            },
-        ];
-
-        let mut attempts = 0;
-        let mut delay = Duration::from_secs(4);
-        let result;
-
-        loop {
-            attempts += 1;
-            let call_result = self.call_llm(messages.clone()).await;
-
-            if call_result.is_ok() {
-                result = call_result;
-                break;
-            }
+        let messages = prompt_messages(system_prompt, user_prompt);
+        let result = with_retries(|| self.call_llm(messages.clone())).await;
+
+        if let Ok(ref response) = result {
+            self.log_call(system_prompt, user_prompt, response.clone(), start_time);
+        }
+        result
+    }
 
-            if attempts >= 10 {
-                result = call_result; // Return the last error
-                break;
+    /// Like `generate_text`, but offers `tools` to the model and returns its tool calls.
+    pub async fn generate_with_tools(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        tools: &[Value],
+    ) -> Result<ToolResponse> {
+        let start_time = std::time::Instant::now();
+        println!("===== USER PROMPT =====\n{}", user_prompt);
+
+        let messages = prompt_messages(system_prompt, user_prompt);
+        let result = with_retries(|| self.call_llm_with_tools(&messages, tools)).await;
+
+        if let Ok(ref response) = result {
+            let mut logged = response.content.clone();
+            for call in &response.tool_calls {
+                logged.push_str(&format!(
+                    "\n[tool call] {}({})",
+                    call.function.name, call.function.arguments
+                ));
             }
+            self.log_call(system_prompt, user_prompt, logged, start_time);
+        }
+        result
+    }
 
-            eprintln!(
-                "LLM call failed (attempt {}/10). Retrying in {:?}. Error: {:?}",
-                attempts, delay, call_result
+    fn log_call(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        response: String,
+        start_time: std::time::Instant,
+    ) {
+        // Log the LLM call if monitor is available
+        if let Some(monitor) = &self.monitor {
+            monitor.log_llm_call(
+                system_prompt.to_string(),
+                user_prompt.to_string(),
+                response,
+                start_time.elapsed().as_millis() as u64,
+                self.model_name.clone(),
             );
-            sleep(delay).await;
-            delay *= 2;
         }
+    }
+}
 
-        let duration_ms = start_time.elapsed().as_millis() as u64;
+/// Secrets are masked before anything is sent to the model.
+fn prompt_messages(system_prompt: &str, user_prompt: &str) -> Vec<ChatCompletionMessage> {
+    vec![
+        ChatCompletionMessage {
+            role: ChatCompletionMessageRole::System,
+            content: Some(redact(system_prompt)),
+            name: None,
+            function_call: None,
+            tool_call_id: None,
+            tool_calls: None,
+        },
+        ChatCompletionMessage {
+            role: ChatCompletionMessageRole::User,
+            content: Some(redact(user_prompt)),
+            name: None,
+            function_call: None,
+            tool_call_id: None,
+            tool_calls: None,
+        },
+    ]
+}
 
-        // Log the LLM call if monitor is available
-        if let Some(monitor) = &self.monitor {
-            if let Ok(ref response) = result {
-                monitor.log_llm_call(
-                    system_prompt.to_string(),
-                    user_prompt.to_string(),
-                    response.clone(),
-                    duration_ms,
-                    self.model_name.clone(),
-                );
-            }
+/// Retries a failing LLM call up to 10 times with exponential backoff.
+async fn with_retries<T, F, Fut>(mut call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = 0;
+    let mut delay = Duration::from_secs(4);
+
+    loop {
+        attempts += 1;
+        let call_result = call().await;
+
+        if call_result.is_ok() || attempts >= 10 {
+            return call_result; // Success or the last error
         }
 
-        result
+        if let Err(e) = &call_result {
+            eprintln!(
+                "LLM call failed (attempt {}/10). Retrying in {:?}. Error: {:?}",
+                attempts, delay, e
+            );
+        }
+        sleep(delay).await;
+        delay *= 2;
     }
 }
@@ -1,4 +1,7 @@
 use crate::actions::Actions;
+use crate::agent::Event;
+use crate::github::RateLimit;
+use crate::redact::redact;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
@@ -21,10 +24,33 @@ pub struct LlmCallLog {
     pub model: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLog {
+    pub timestamp: DateTime<Utc>,
+    pub event: Event,
+}
+
+/// A GitHub write that was only simulated because of `PRISTINE_DRY_RUN`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunLog {
+    pub timestamp: DateTime<Utc>,
+    pub action: Actions,
+    pub simulated_result: String,
+}
+
 #[derive(Clone)]
 pub struct Monitor {
     action_logs: Arc<Mutex<Vec<ActionLog>>>,
     llm_call_logs: Arc<Mutex<Vec<LlmCallLog>>>,
+    event_logs: Arc<Mutex<Vec<EventLog>>>,
+    dry_run_logs: Arc<Mutex<Vec<DryRunLog>>>,
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Monitor {
@@ -32,6 +58,9 @@ impl Monitor {
         Self {
             action_logs: Arc::new(Mutex::new(Vec::new())),
             llm_call_logs: Arc::new(Mutex::new(Vec::new())),
+            event_logs: Arc::new(Mutex::new(Vec::new())),
+            dry_run_logs: Arc::new(Mutex::new(Vec::new())),
+            rate_limit: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -39,7 +68,7 @@ impl Monitor {
         let log = ActionLog {
             timestamp: Utc::now(),
             action,
-            result,
+            result: redact(&result),
             duration_ms,
         };
 
@@ -63,9 +92,9 @@ impl Monitor {
     ) {
         let log = LlmCallLog {
             timestamp: Utc::now(),
-            system_prompt,
-            user_prompt,
-            response,
+            system_prompt: redact(&system_prompt),
+            user_prompt: redact(&user_prompt),
+            response: redact(&response),
             duration_ms,
             model,
         };
@@ -80,6 +109,46 @@ impl Monitor {
         }
     }
 
+    pub fn log_event(&self, event: Event) {
+        let log = EventLog {
+            timestamp: Utc::now(),
+            event,
+        };
+
+        if let Ok(mut logs) = self.event_logs.lock() {
+            logs.push(log);
+            // Keep only last 1000 entries to prevent memory issues
+            if logs.len() > 1000 {
+                let drain_count = logs.len() - 1000;
+                logs.drain(0..drain_count);
+            }
+        }
+    }
+
+    pub fn log_dry_run(&self, action: Actions, simulated_result: String) {
+        let log = DryRunLog {
+            timestamp: Utc::now(),
+            action,
+            simulated_result: redact(&simulated_result),
+        };
+
+        if let Ok(mut logs) = self.dry_run_logs.lock() {
+            logs.push(log);
+            // Keep only last 1000 entries to prevent memory issues
+            if logs.len() > 1000 {
+                let drain_count = logs.len() - 1000;
+                logs.drain(0..drain_count);
+            }
+        }
+    }
+
+    /// Records the GitHub quota left, as reported by the latest response.
+    pub fn set_rate_limit(&self, rate_limit: RateLimit) {
+        if let Ok(mut current) = self.rate_limit.lock() {
+            *current = Some(rate_limit);
+        }
+    }
+
     pub fn get_action_logs(&self) -> Vec<ActionLog> {
         self.action_logs
             .lock()
@@ -93,4 +162,25 @@ impl Monitor {
             .unwrap_or_else(|e| e.into_inner())
             .clone()
     }
+
+    pub fn get_event_logs(&self) -> Vec<EventLog> {
+        self.event_logs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn get_dry_run_logs(&self) -> Vec<DryRunLog> {
+        self.dry_run_logs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn get_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 }
@@ -0,0 +1,241 @@
+use std::path::Path;
+
+/// A navigable item of a source file, such as a function or a heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    /// 1-based line number.
+    pub line: usize,
+    /// Nesting level, e.g. 1 for a method inside an `impl`.
+    pub depth: usize,
+    pub text: String,
+}
+
+/// Extracts the outline of a file, choosing the rules from its extension.
+/// Returns `None` for file types without outline rules.
+pub fn outline(path: &str, content: &str) -> Option<Vec<OutlineItem>> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let items = match extension.as_str() {
+        "rs" => rust_outline(content),
+        "md" | "markdown" => markdown_outline(content),
+        "py" => indented_outline(content, &["def ", "async def ", "class "]),
+        "go" => indented_outline(content, &["func ", "type "]),
+        "js" | "jsx" | "ts" | "tsx" | "mjs" => indented_outline(
+            content,
+            &[
+                "function ",
+                "async function ",
+                "class ",
+                "export ",
+                "interface ",
+                "type ",
+            ],
+        ),
+        _ => return None,
+    };
+    Some(items)
+}
+
+/// Renders an outline as `  line: text` rows, indented by depth.
+pub fn render(items: &[OutlineItem]) -> String {
+    items
+        .iter()
+        .map(|item| {
+            format!(
+                "{:>5}: {}{}",
+                item.line,
+                "    ".repeat(item.depth),
+                item.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const RUST_ITEMS: &[&str] = &[
+    "fn ",
+    "struct ",
+    "enum ",
+    "trait ",
+    "impl",
+    "mod ",
+    "type ",
+    "const ",
+    "static ",
+    "union ",
+    "macro_rules!",
+];
+
+const RUST_MODIFIERS: &[&str] = &["pub ", "async ", "unsafe ", "const ", "extern \"C\" "];
+
+/// Top-level Rust items, plus the functions one level down inside `impl`, `trait` and `mod`.
+fn rust_outline(content: &str) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let indent = line.len() - line.trim_start().len();
+        let depth = match indent {
+            0 => 0,
+            4 => 1,
+            _ => continue,
+        };
+        let trimmed = line.trim();
+        let item = strip_rust_modifiers(trimmed);
+        let is_item = if depth == 0 {
+            RUST_ITEMS.iter().any(|prefix| {
+                item.starts_with(prefix)
+                    // `impl<T>` and `impl Trait`, not identifiers like `implement`
+                    && (*prefix != "impl" || item[4..].starts_with([' ', '<']))
+            })
+        } else {
+            item.starts_with("fn ")
+        };
+        if is_item {
+            items.push(OutlineItem {
+                line: i + 1,
+                depth,
+                text: signature(trimmed),
+            });
+        }
+    }
+    items
+}
+
+fn strip_rust_modifiers(mut item: &str) -> &str {
+    // `pub(crate) `, `pub(super) `...
+    if item.starts_with("pub(")
+        && let Some(end) = item.find(") ")
+    {
+        item = &item[end + 2..];
+    }
+    loop {
+        match RUST_MODIFIERS.iter().find(|m| item.starts_with(*m)) {
+            // `const fn` is a function, a bare `const` is an item of its own
+            Some(m) if *m != "const " || item[m.len()..].starts_with("fn ") => {
+                item = &item[m.len()..]
+            }
+            _ => return item,
+        }
+    }
+}
+
+fn markdown_outline(content: &str) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    let mut in_code_block = false;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if !in_code_block && (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            items.push(OutlineItem {
+                line: i + 1,
+                depth: level - 1,
+                text: line.to_string(),
+            });
+        }
+    }
+    items
+}
+
+/// Lines starting with one of `prefixes`, nested by their indentation.
+fn indented_outline(content: &str, prefixes: &[&str]) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    let mut indents: Vec<usize> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if !prefixes.iter().any(|p| trimmed.starts_with(p)) {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        while indents.last().is_some_and(|last| *last >= indent) {
+            indents.pop();
+        }
+        items.push(OutlineItem {
+            line: i + 1,
+            depth: indents.len(),
+            text: signature(trimmed),
+        });
+        indents.push(indent);
+    }
+    items
+}
+
+/// The declaration without its body.
+fn signature(line: &str) -> String {
+    line.trim_end()
+        .trim_end_matches('{')
+        .trim_end_matches(':')
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_outline() {
+        let content = "use std::fs;\n\
+                       \n\
+                       pub(crate) struct Agent {\n\
+                       \x20   name: String,\n\
+                       }\n\
+                       \n\
+                       impl Agent {\n\
+                       \x20   pub async fn start(&self) {\n\
+                       \x20       let implement = 1;\n\
+                       \x20   }\n\
+                       }\n\
+                       \n\
+                       pub const fn limit() -> usize { 3 }\n\
+                       const MAX: usize = 5;\n";
+        let items = outline("src/agent.rs", content).unwrap();
+        assert_eq!(
+            render(&items),
+            "    3: pub(crate) struct Agent\n\
+             \x20   7: impl Agent\n\
+             \x20   8:     pub async fn start(&self)\n\
+             \x20  13: pub const fn limit() -> usize { 3 }\n\
+             \x20  14: const MAX: usize = 5;"
+        );
+    }
+
+    #[test]
+    fn test_markdown_outline() {
+        let content = "# Title\ntext\n```sh\n# comment\n```\n## Usage\n#hashtag\n";
+        let items = outline("README.md", content).unwrap();
+        assert_eq!(
+            items,
+            vec![
+                OutlineItem {
+                    line: 1,
+                    depth: 0,
+                    text: "# Title".to_string()
+                },
+                OutlineItem {
+                    line: 6,
+                    depth: 1,
+                    text: "## Usage".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_outline() {
+        let content = "import os\n\nclass Repo:\n    def read(self):\n        pass\n\ndef main():\n    pass\n";
+        let items = outline("main.py", content).unwrap();
+        assert_eq!(
+            render(&items),
+            "    3: class Repo\n    4:     def read(self)\n    7: def main()"
+        );
+    }
+
+    #[test]
+    fn test_unknown_extension() {
+        assert!(outline("data.bin", "").is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use serde_json::Value;
+
+/// Checks `value` against the subset of JSON Schema used to describe actions:
+/// `type`, `const`, `enum`, `minimum`, `items`, `properties`, `required` and
+/// `additionalProperties: false`.
+/// Each error is prefixed with the path of the offending field, e.g. `sleep.duration`.
+pub fn validate(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push(format!("{}: expected {}, got {}", path, expected, value));
+        return errors;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+        errors.push(format!(
+            "{}: expected one of {}, got {}",
+            path,
+            allowed.join(", "),
+            value
+        ));
+        return errors;
+    }
+
+    // `type` is a name, or a list of names for nullable fields
+    let expected: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    if !expected.is_empty() && !expected.iter().any(|name| has_type(value, name)) {
+        errors.push(format!(
+            "{}: expected {}, got {} {}",
+            path,
+            expected.join(" or "),
+            type_name(value),
+            value
+        ));
+        return errors;
+    }
+
+    if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+        && let Some(number) = value.as_f64()
+        && number < minimum
+    {
+        errors.push(format!(
+            "{}: must be at least {}, got {}",
+            path, minimum, value
+        ));
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            errors.extend(validate(items, item, &format!("{}[{}]", path, i)));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(required) {
+                errors.push(format!("{}.{}: missing required field", path, required));
+            }
+        }
+
+        for (key, field) in object {
+            let field_path = format!("{}.{}", path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(field_schema) => errors.extend(validate(field_schema, field, &field_path)),
+                None if schema["additionalProperties"] == Value::Bool(false) => {
+                    errors.push(format!("{}: unknown field", field_path));
+                }
+                None => {}
+            }
+        }
+    }
+
+    errors
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "integer" => value.is_u64() || value.is_i64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn issue_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "issue_number": {"type": "integer", "minimum": 0},
+                "labels": {"type": "array", "items": {"type": "string"}},
+                "state": {"type": "string", "enum": ["open", "closed"]},
+                "limit": {"type": ["integer", "null"]},
+            },
+            "required": ["issue_number"],
+            "additionalProperties": false,
+        })
+    }
+
+    #[test]
+    fn test_valid_value() {
+        let value = json!({"issue_number": 3, "labels": ["bug"], "state": "open", "limit": null});
+        assert!(validate(&issue_schema(), &value, "issue").is_empty());
+        assert_eq!(
+            validate(
+                &issue_schema(),
+                &json!({"issue_number": 3, "limit": "5"}),
+                "issue"
+            ),
+            vec!["issue.limit: expected integer or null, got string \"5\""]
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let value = json!({"issue_number": "3", "labels": ["bug", 1], "state": "merged", "x": 1});
+        let errors = validate(&issue_schema(), &value, "issue");
+        assert_eq!(
+            errors,
+            vec![
+                "issue.issue_number: expected integer, got string \"3\"",
+                "issue.labels[1]: expected string, got number 1",
+                "issue.state: expected one of \"open\", \"closed\", got \"merged\"",
+                "issue.x: unknown field",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_and_negative_fields() {
+        let schema = issue_schema();
+        assert_eq!(
+            validate(&schema, &json!({}), "issue"),
+            vec!["issue.issue_number: missing required field"]
+        );
+        assert_eq!(
+            validate(&schema, &json!({"issue_number": -1}), "issue"),
+            vec!["issue.issue_number: must be at least 0, got -1"]
+        );
+    }
+}
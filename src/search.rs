@@ -0,0 +1,195 @@
+use regex::Regex;
+
+/// Default number of matching lines returned by a search.
+pub const DEFAULT_MAX_RESULTS: usize = 50;
+/// Upper bound on matching lines, whatever the model asks for.
+pub const MAX_RESULTS_LIMIT: usize = 200;
+/// Upper bound on context lines around each match.
+pub const MAX_CONTEXT_LINES: usize = 5;
+/// Longer lines are cut, minified files would otherwise flood the prompt.
+const MAX_LINE_CHARS: usize = 200;
+
+/// A line of a search result, either a match or context around one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchLine {
+    /// 1-based line number.
+    pub line: usize,
+    pub text: String,
+    pub is_match: bool,
+}
+
+/// The lines of one file around its matches, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: String,
+    pub lines: Vec<SearchLine>,
+}
+
+/// Finds the lines of `content` matching `regex`, with `context` lines around them.
+/// Stops after `limit` matching lines; returns the lines and the number of matches kept.
+pub fn search_content(
+    regex: &Regex,
+    content: &str,
+    context: usize,
+    limit: usize,
+) -> (Vec<SearchLine>, usize) {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut result: Vec<SearchLine> = Vec::new();
+    let mut matches = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if matches == limit {
+            break;
+        }
+        if !regex.is_match(line) {
+            continue;
+        }
+        matches += 1;
+        let first = i.saturating_sub(context);
+        let last = (i + context).min(lines.len() - 1);
+        for (j, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+            if result.last().is_some_and(|previous| previous.line > j) {
+                // Already shown as context of the previous match
+                if j == i
+                    && let Some(shown) = result.iter_mut().rev().find(|l| l.line == j + 1)
+                {
+                    shown.is_match = true;
+                }
+                continue;
+            }
+            result.push(SearchLine {
+                line: j + 1,
+                text: shorten(text),
+                is_match: j == i,
+            });
+        }
+    }
+    (result, matches)
+}
+
+/// Renders results grouped by file, ripgrep style: `12:` for matches, `13-`
+/// for context and `--` between separate hunks.
+pub fn render(files: &[FileMatches]) -> String {
+    let mut output = String::new();
+    for file in files {
+        output.push_str(&file.path);
+        output.push('\n');
+        let mut previous: Option<usize> = None;
+        for line in &file.lines {
+            if previous.is_some_and(|p| p + 1 < line.line) {
+                output.push_str("  --\n");
+            }
+            let separator = if line.is_match { ':' } else { '-' };
+            output.push_str(&format!("  {}{} {}\n", line.line, separator, line.text));
+            previous = Some(line.line);
+        }
+    }
+    output.trim_end().to_string()
+}
+
+/// Matches a repository-relative path against a glob: `*` and `?` stay within a
+/// path segment, `**` spans directories and `{a,b}` lists alternatives.
+/// Patterns without a `/` are matched against the file name only, like `*.rs`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let target = if pattern.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    pattern_match(pattern, target)
+}
+
+/// Matches the whole of `text` against a glob, with the rules of `glob_match`.
+/// Used for names with slashes that are not paths, such as `release/*` branches.
+pub fn pattern_match(pattern: &str, text: &str) -> bool {
+    expand_braces(pattern)
+        .iter()
+        .any(|p| wildcard_match(p.as_bytes(), text.as_bytes()))
+}
+
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|c| open + c) else {
+        return vec![pattern.to_string()];
+    };
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alternative| {
+            expand_braces(&format!(
+                "{}{}{}",
+                &pattern[..open],
+                alternative,
+                &pattern[close + 1..]
+            ))
+        })
+        .collect()
+}
+
+fn wildcard_match(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // `**/` also matches no directory at all
+            wildcard_match(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .any(|(i, c)| *c == b'/' && wildcard_match(rest, &path[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| wildcard_match(rest, &path[i..])),
+        [b'*', rest @ ..] => {
+            let segment = path.iter().position(|c| *c == b'/').unwrap_or(path.len());
+            (0..=segment).any(|i| wildcard_match(rest, &path[i..]))
+        }
+        [b'?', rest @ ..] => path
+            .first()
+            .is_some_and(|c| *c != b'/' && wildcard_match(rest, &path[1..])),
+        [c, rest @ ..] => path.first() == Some(c) && wildcard_match(rest, &path[1..]),
+    }
+}
+
+fn shorten(line: &str) -> String {
+    let line = line.trim_end();
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((cut, _)) => format!("{}...", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.rs", "src/agent.rs"));
+        assert!(!glob_match("*.rs", "README.md"));
+        assert!(glob_match("src/*.rs", "src/agent.rs"));
+        assert!(!glob_match("src/*.rs", "src/bin/run.rs"));
+        assert!(glob_match("src/**/*.rs", "src/bin/run.rs"));
+        assert!(glob_match("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_match("*.{rs,toml}", "Cargo.toml"));
+        assert!(glob_match("?ib.rs", "src/lib.rs"));
+    }
+
+    #[test]
+    fn test_search_content_merges_context() {
+        let content = "a\nTODO one\nb\nTODO two\nc\nd\ne\nTODO three\n";
+        let regex = Regex::new("TODO").unwrap();
+        let (lines, matches) = search_content(&regex, content, 1, 10);
+        assert_eq!(matches, 3);
+        let files = vec![FileMatches {
+            path: "notes.txt".to_string(),
+            lines,
+        }];
+        assert_eq!(
+            render(&files),
+            "notes.txt\n  1- a\n  2: TODO one\n  3- b\n  4: TODO two\n  5- c\n  --\n  7- e\n  8: TODO three"
+        );
+
+        let (lines, matches) = search_content(&regex, content, 0, 2);
+        assert_eq!(matches, 2);
+        assert_eq!(lines.len(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::Event;
+
+/// Version of the on-disk state format.
+/// Bump this and add a step to `migrate` whenever `PersistedState` changes shape.
+pub const STATE_VERSION: u32 = 2;
+
+const STATE_FILE_NAME: &str = "state.json";
+
+/// The part of the agent context that survives restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub version: u32,
+    #[serde(default)]
+    pub memories: HashMap<String, String>,
+    #[serde(default)]
+    pub past_events: Vec<Event>,
+    #[serde(default)]
+    pub last_thought: Option<String>,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            memories: HashMap::new(),
+            past_events: Vec::new(),
+            last_thought: None,
+        }
+    }
+}
+
+/// Reads and writes `PersistedState` as a JSON file inside the data directory.
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let data_dir = data_dir.as_ref();
+        fs::create_dir_all(data_dir).context(format!(
+            "Failed to create data directory {}",
+            data_dir.display()
+        ))?;
+        Ok(Self {
+            path: data_dir.join(STATE_FILE_NAME),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the state, upgrading older formats on the fly.
+    /// A missing file yields an empty state.
+    pub fn load(&self) -> Result<PersistedState> {
+        if !self.path.exists() {
+            return Ok(PersistedState::default());
+        }
+        let content = fs::read_to_string(&self.path)
+            .context(format!("Failed to read state file {}", self.path.display()))?;
+        let value: Value = serde_json::from_str(&content).context(format!(
+            "Failed to parse state file {}",
+            self.path.display()
+        ))?;
+        let value = migrate(value)?;
+        serde_json::from_value(value).context("Failed to decode migrated state")
+    }
+
+    /// Writes the state atomically by going through a temporary file.
+    pub fn save(&self, state: &PersistedState) -> Result<()> {
+        let json = serde_json::to_string_pretty(state).context("Failed to serialize state")?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)
+            .context(format!("Failed to write state file {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path).context(format!(
+            "Failed to replace state file {}",
+            self.path.display()
+        ))?;
+        Ok(())
+    }
+}
+
+/// Brings a raw state document up to `STATE_VERSION`, one version at a time.
+fn migrate(mut value: Value) -> Result<Value> {
+    // Files without a version field predate versioning and share the v1 layout.
+    let mut version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .map(|v| v as u32)
+        .unwrap_or(1);
+
+    if version > STATE_VERSION {
+        return Err(anyhow!(
+            "State file version {} is newer than supported version {}",
+            version,
+            STATE_VERSION
+        ));
+    }
+
+    while version < STATE_VERSION {
+        value = match version {
+            1 => migrate_v1_to_v2(value),
+            v => return Err(anyhow!("No migration from state version {}", v)),
+        };
+        version += 1;
+    }
+
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("version".to_string(), Value::from(STATE_VERSION));
+    }
+    Ok(value)
+}
+
+/// v1 stored past events as free-form strings, which cannot be turned back into
+/// typed events, so they are dropped.
+fn migrate_v1_to_v2(mut value: Value) -> Value {
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("past_events".to_string(), Value::Array(vec![]));
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_loads_default() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = StateStore::new(dir.path()).unwrap();
+        assert_eq!(store.load().unwrap(), PersistedState::default());
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = StateStore::new(dir.path()).unwrap();
+        let mut state = PersistedState::default();
+        state
+            .memories
+            .insert("project".to_string(), "Rust agent".to_string());
+        state.past_events.push(Event::NewIssue {
+            issue_number: 1,
+            title: "Test".to_string(),
+            author: "octocat".to_string(),
+            body: "Body".to_string(),
+        });
+        state.last_thought = Some("Read the README".to_string());
+
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap(), state);
+    }
+
+    #[test]
+    fn test_unversioned_file_loads() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = StateStore::new(dir.path()).unwrap();
+        fs::write(store.path(), r#"{"memories": {"name": "Pristine"}}"#).unwrap();
+
+        let state = store.load().unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.memories.get("name").unwrap(), "Pristine");
+    }
+
+    #[test]
+    fn test_v1_file_is_migrated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = StateStore::new(dir.path()).unwrap();
+        fs::write(
+            store.path(),
+            r#"{"version": 1, "memories": {"name": "Pristine"}, "past_events": ["New issue: #1 - Test"], "last_thought": "Hello"}"#,
+        )
+        .unwrap();
+
+        let state = store.load().unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.memories.get("name").unwrap(), "Pristine");
+        assert!(state.past_events.is_empty());
+        assert_eq!(state.last_thought.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = StateStore::new(dir.path()).unwrap();
+        fs::write(
+            store.path(),
+            format!(r#"{{"version": {}}}"#, STATE_VERSION + 1),
+        )
+        .unwrap();
+
+        assert!(store.load().is_err());
+    }
+}
@@ -1,16 +1,66 @@
+use crate::actions::Actions;
+use crate::approval::ApprovalQueue;
 use crate::monitoring::Monitor;
+use crate::redact::redact;
+use crate::webhook::{self, WebhookEvent};
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::UnboundedSender;
+
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+/// The dashboard and approval endpoints only take small JSON documents.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+// GitHub caps webhook payloads at 25 MB
+const MAX_WEBHOOK_BODY_SIZE: usize = 25 * 1024 * 1024;
+
+#[derive(Clone)]
+struct WebhookReceiver {
+    path: String,
+    secret: String,
+    sender: UnboundedSender<WebhookEvent>,
+}
 
 pub struct WebServer {
     monitor: Arc<Monitor>,
     port: u16,
+    webhook: Option<WebhookReceiver>,
+    approvals: Option<ApprovalQueue>,
+    admin_token: Option<String>,
 }
 
 impl WebServer {
     pub fn new(monitor: Arc<Monitor>, port: u16) -> Self {
-        Self { monitor, port }
+        Self {
+            monitor,
+            port,
+            webhook: None,
+            approvals: None,
+            admin_token: None,
+        }
+    }
+
+    /// Accepts GitHub webhook deliveries on `path` and forwards the verified ones to `sender`.
+    pub fn with_webhook(
+        mut self,
+        path: String,
+        secret: String,
+        sender: UnboundedSender<WebhookEvent>,
+    ) -> Self {
+        self.webhook = Some(WebhookReceiver {
+            path,
+            secret,
+            sender,
+        });
+        self
+    }
+
+    /// Lists the actions waiting for approval and lets maintainers approve, reject or edit them.
+    /// Requests must carry `admin_token` as bearer token, and are refused when it is `None`.
+    pub fn with_approvals(mut self, approvals: ApprovalQueue, admin_token: Option<String>) -> Self {
+        self.approvals = Some(approvals);
+        self.admin_token = admin_token;
+        self
     }
 
     pub async fn start(&self) -> anyhow::Result<()> {
@@ -18,28 +68,45 @@ impl WebServer {
         let listener = TcpListener::bind(&addr).await?;
         println!("Dashboard server running at http://{}", addr);
 
+        if let Some(webhook) = &self.webhook {
+            println!(
+                "Accepting GitHub webhooks at http://{}{}",
+                addr, webhook.path
+            );
+        }
+
         let monitor = self.monitor.clone();
+        let webhook = self.webhook.clone();
+        let approvals = self.approvals.clone();
+        let admin_token = self.admin_token.clone();
 
         tokio::spawn(async move {
             loop {
                 let (mut socket, _) = listener.accept().await.unwrap();
                 let monitor = monitor.clone();
+                let webhook = webhook.clone();
+                let approvals = approvals.clone();
+                let admin_token = admin_token.clone();
 
                 tokio::spawn(async move {
-                    let mut buffer = [0; 1024];
-                    let n = socket.read(&mut buffer).await.unwrap();
-                    let request = String::from_utf8_lossy(&buffer[..n]);
-
-                    let response = if request.starts_with("GET / ")
-                        || request.starts_with("GET /index.html")
-                    {
-                        Self::serve_dashboard()
-                    } else if request.starts_with("GET /api/actions") {
-                        Self::serve_actions(&monitor)
-                    } else if request.starts_with("GET /api/llm-calls") {
-                        Self::serve_llm_calls(&monitor)
-                    } else {
-                        Self::not_found()
+                    let Ok((request, body)) =
+                        Self::read_request(&mut socket, webhook.as_ref()).await
+                    else {
+                        return;
+                    };
+
+                    let response = match body {
+                        Some(body) => Self::route(
+                            &request,
+                            &body,
+                            &monitor,
+                            webhook.as_ref(),
+                            approvals.as_ref(),
+                            admin_token.as_deref(),
+                        ),
+                        None => {
+                            Self::plain_response("413 Payload Too Large", "Request body too large")
+                        }
                     };
 
                     socket.write_all(response.as_bytes()).await.unwrap();
@@ -51,6 +118,212 @@ impl WebServer {
         Ok(())
     }
 
+    fn route(
+        request: &str,
+        body: &[u8],
+        monitor: &Monitor,
+        webhook: Option<&WebhookReceiver>,
+        approvals: Option<&ApprovalQueue>,
+        admin_token: Option<&str>,
+    ) -> String {
+        if let Some(webhook) = webhook.filter(|w| request.starts_with(&format!("POST {} ", w.path)))
+        {
+            Self::handle_webhook(webhook, request, body)
+        } else if request.starts_with("GET / ") || request.starts_with("GET /index.html") {
+            Self::serve_dashboard()
+        } else if request.starts_with("GET /api/actions") {
+            Self::serve_actions(monitor)
+        } else if request.starts_with("GET /api/llm-calls") {
+            Self::serve_llm_calls(monitor)
+        } else if request.starts_with("GET /api/events") {
+            Self::serve_events(monitor)
+        } else if request.starts_with("GET /api/dry-run") {
+            Self::serve_dry_run(monitor)
+        } else if request.starts_with("GET /api/rate-limit") {
+            Self::serve_rate_limit(monitor)
+        } else if let Some(approvals) = approvals.filter(|_| request.contains(" /api/approvals")) {
+            match admin_token {
+                None => Self::plain_response(
+                    "403 Forbidden",
+                    "Set PRISTINE_ADMIN_TOKEN to enable the approval endpoints",
+                ),
+                Some(token) if !Self::is_authorized(request, token) => {
+                    Self::plain_response("401 Unauthorized", "Invalid or missing admin token")
+                }
+                Some(_) => Self::handle_approvals(approvals, request, body),
+            }
+        } else {
+            Self::not_found()
+        }
+    }
+
+    /// Reads the request head and, when a Content-Length is given, the full body.
+    /// The body is `None` when it is larger than `body_limit` allows.
+    async fn read_request(
+        socket: &mut TcpStream,
+        webhook: Option<&WebhookReceiver>,
+    ) -> std::io::Result<(String, Option<Vec<u8>>)> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        let header_end = loop {
+            let n = socket.read(&mut chunk).await?;
+            if n == 0 {
+                break buffer.len();
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            if buffer.len() > MAX_HEADER_SIZE {
+                break buffer.len();
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+        let content_length = Self::header_value(&head, "content-length")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        if content_length > Self::body_limit(&head, webhook) {
+            return Ok((head, None));
+        }
+        let mut body = buffer[header_end..].to_vec();
+        while body.len() < content_length {
+            let n = socket.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+        body.truncate(content_length);
+        Ok((head, Some(body)))
+    }
+
+    /// Largest body accepted for a request. Only a webhook delivery that carries
+    /// GitHub's signature and event headers may be large, and it is verified
+    /// once read.
+    fn body_limit(head: &str, webhook: Option<&WebhookReceiver>) -> usize {
+        let is_delivery = webhook.is_some_and(|w| head.starts_with(&format!("POST {} ", w.path)))
+            && Self::header_value(head, "x-hub-signature-256").is_some()
+            && Self::header_value(head, "x-github-event").is_some();
+        if is_delivery {
+            MAX_WEBHOOK_BODY_SIZE
+        } else {
+            MAX_BODY_SIZE
+        }
+    }
+
+    fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then_some(value.trim())
+        })
+    }
+
+    /// Whether the request carries `Authorization: Bearer <token>`. The custom
+    /// header also keeps other sites from sending the request through a browser.
+    fn is_authorized(request: &str, token: &str) -> bool {
+        let Some(given) = Self::header_value(request, "authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        // Compare in constant time so the token cannot be guessed byte by byte
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn handle_webhook(webhook: &WebhookReceiver, request: &str, body: &[u8]) -> String {
+        let signature = Self::header_value(request, "x-hub-signature-256").unwrap_or_default();
+        if !webhook::verify_signature(&webhook.secret, body, signature) {
+            println!("Rejected webhook delivery with an invalid signature.");
+            return Self::plain_response("401 Unauthorized", "Invalid signature");
+        }
+
+        let event_type = Self::header_value(request, "x-github-event").unwrap_or_default();
+        match webhook::parse_delivery(event_type, body) {
+            Ok(Some(event)) => {
+                println!("Received '{}' webhook delivery.", event_type);
+                if webhook.sender.send(event).is_err() {
+                    return Self::plain_response("503 Service Unavailable", "Agent is not running");
+                }
+                Self::plain_response("202 Accepted", "Accepted")
+            }
+            Ok(None) => Self::plain_response("200 OK", "Ignored"),
+            Err(e) => {
+                println!("Failed to parse '{}' webhook delivery: {}", event_type, e);
+                Self::plain_response("400 Bad Request", "Invalid payload")
+            }
+        }
+    }
+
+    /// `GET /api/approvals`, and `POST /api/approvals/{id}/approve`, `/reject` with an
+    /// optional `{"reason": ".."}` body, or `/edit` with the replacement action as body.
+    fn handle_approvals(approvals: &ApprovalQueue, request: &str, body: &[u8]) -> String {
+        let request_line = request.lines().next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+
+        if method == "GET" && path == "/api/approvals" {
+            let json = redact(
+                &serde_json::to_string(&approvals.list()).unwrap_or_else(|_| "[]".to_string()),
+            );
+            return format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                json.len(),
+                json
+            );
+        }
+
+        let route = path
+            .strip_prefix("/api/approvals/")
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(id, verb)| Some((id.parse::<u64>().ok()?, verb)));
+        let (id, verb) = match (method, route) {
+            ("POST", Some(route)) => route,
+            _ => return Self::not_found(),
+        };
+
+        let result = match verb {
+            "approve" => approvals.approve(id),
+            "reject" => {
+                let reason = serde_json::from_slice::<serde_json::Value>(body)
+                    .ok()
+                    .and_then(|v| v["reason"].as_str().map(str::to_string))
+                    .filter(|r| !r.trim().is_empty())
+                    .unwrap_or_else(|| "No reason given".to_string());
+                approvals.reject(id, reason)
+            }
+            "edit" => serde_json::from_slice::<serde_json::Value>(body)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| Actions::from_json(&value))
+                .and_then(|action| approvals.edit(id, action)),
+            _ => return Self::not_found(),
+        };
+        match result {
+            Ok(()) => Self::plain_response("200 OK", "OK"),
+            Err(e) => Self::plain_response("400 Bad Request", &e.to_string()),
+        }
+    }
+
+    fn plain_response(status: &str, body: &str) -> String {
+        let body = redact(body);
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
     fn serve_dashboard() -> String {
         let html = r#"<!DOCTYPE html>
 <html lang="en">
@@ -251,6 +524,16 @@ impl WebServer {
             color: #7f8c8d;
         }
         
+        .rate-limit {
+            text-align: center;
+            margin: -20px 0 20px;
+            color: #7f8c8d;
+        }
+        
+        .rate-limit.low {
+            color: #e74c3c;
+        }
+        
         .error {
             background: #e74c3c;
             color: white;
@@ -263,10 +546,14 @@ impl WebServer {
 <body>
     <div class="container">
         <h1>Agent Monitoring Dashboard</h1>
+        <div id="rate-limit" class="rate-limit"></div>
         
         <div class="tabs">
             <button class="tab active" onclick="showTab('actions')">Action History</button>
             <button class="tab" onclick="showTab('llm')">LLM Call History</button>
+            <button class="tab" onclick="showTab('events')">Events</button>
+            <button class="tab" onclick="showTab('dry-run')">Dry Run</button>
+            <button class="tab" onclick="showTab('approvals')">Approvals</button>
         </div>
         
         <div id="actions" class="tab-content active">
@@ -276,6 +563,18 @@ impl WebServer {
         <div id="llm" class="tab-content">
             <div class="loading">Loading LLM call history...</div>
         </div>
+        
+        <div id="events" class="tab-content">
+            <div class="loading">Loading events...</div>
+        </div>
+        
+        <div id="dry-run" class="tab-content">
+            <div class="loading">Loading dry run writes...</div>
+        </div>
+        
+        <div id="approvals" class="tab-content">
+            <div class="loading">Loading approval requests...</div>
+        </div>
     </div>
     
     <button class="refresh-btn" onclick="refreshData()">Refresh</button>
@@ -297,6 +596,12 @@ impl WebServer {
 
             if (tab === 'actions') {
                 loadActions();
+            } else if (tab === 'events') {
+                loadEvents();
+            } else if (tab === 'dry-run') {
+                loadDryRun();
+            } else if (tab === 'approvals') {
+                loadApprovals();
             } else {
                 loadLLMCalls();
             }
@@ -392,6 +697,169 @@ impl WebServer {
             }
         }
         
+        async function loadEvents() {
+            try {
+                const response = await fetch('/api/events');
+                const data = await response.json();
+                
+                const container = document.getElementById('events');
+                if (data.length === 0) {
+                    container.innerHTML = '<div class="loading">No events recorded yet.</div>';
+                    return;
+                }
+                
+                container.innerHTML = data.slice().reverse().map((entry, index) => {
+                    const kind = getActionName(entry.event);
+                    const detailsId = `event-${index}`;
+                    const isExpanded = expandedStates.has(detailsId) ? '' : 'collapsed';
+                    const buttonText = expandedStates.has(detailsId) ? 'Show Less' : 'Show More';
+                    const details = JSON.stringify(entry.event[kind], null, 2);
+
+                    return `
+                        <div class="log-entry">
+                            <div class="timestamp">${new Date(entry.timestamp).toLocaleString()}</div>
+                            <div class="action-name">${kind}</div>
+                            <div class="prompt-section">
+                                <div id="${detailsId}" class="prompt-content ${isExpanded}">${escapeHtml(details)}</div>
+                                ${details.length > 200 ? `<button class="expand-toggle" onclick="toggleExpand('${detailsId}', this)">${buttonText}</button>` : ''}
+                            </div>
+                        </div>
+                    `;
+                }).join('');
+            } catch (error) {
+                document.getElementById('events').innerHTML = 
+                    '<div class="error">Error loading events: ' + error.message + '</div>';
+            }
+        }
+        
+        async function loadDryRun() {
+            try {
+                const response = await fetch('/api/dry-run');
+                const data = await response.json();
+                
+                const container = document.getElementById('dry-run');
+                if (data.length === 0) {
+                    container.innerHTML = '<div class="loading">No forge writes were simulated. Set PRISTINE_DRY_RUN to enable dry-run mode.</div>';
+                    return;
+                }
+                
+                container.innerHTML = data.slice().reverse().map((entry, index) => {
+                    const actionName = getActionName(entry.action);
+                    const detailsId = `dry-run-${index}`;
+                    const isExpanded = expandedStates.has(detailsId) ? '' : 'collapsed';
+                    const buttonText = expandedStates.has(detailsId) ? 'Show Less' : 'Show More';
+                    const details = JSON.stringify(entry.action[actionName], null, 2);
+
+                    return `
+                        <div class="log-entry">
+                            <div class="timestamp">${new Date(entry.timestamp).toLocaleString()}</div>
+                            <div class="action-name">${actionName} (not sent)</div>
+                            <div class="result">${escapeHtml(entry.simulated_result)}</div>
+                            <div class="prompt-section">
+                                <div id="${detailsId}" class="prompt-content ${isExpanded}">${escapeHtml(details)}</div>
+                                ${details.length > 200 ? `<button class="expand-toggle" onclick="toggleExpand('${detailsId}', this)">${buttonText}</button>` : ''}
+                            </div>
+                        </div>
+                    `;
+                }).join('');
+            } catch (error) {
+                document.getElementById('dry-run').innerHTML = 
+                    '<div class="error">Error loading dry run writes: ' + error.message + '</div>';
+            }
+        }
+        
+        function adminHeaders() {
+            let token = localStorage.getItem('adminToken');
+            if (!token) {
+                token = prompt('Admin token (PRISTINE_ADMIN_TOKEN):');
+                if (token) {
+                    localStorage.setItem('adminToken', token);
+                }
+            }
+            return { 'Authorization': 'Bearer ' + (token || '') };
+        }
+        
+        async function loadApprovals() {
+            try {
+                const response = await fetch('/api/approvals', { headers: adminHeaders() });
+                if (response.status === 401) {
+                    localStorage.removeItem('adminToken');
+                }
+                if (!response.ok) {
+                    throw new Error(await response.text());
+                }
+                const data = await response.json();
+                
+                const container = document.getElementById('approvals');
+                if (data.length === 0) {
+                    container.innerHTML = '<div class="loading">No actions required approval yet.</div>';
+                    return;
+                }
+                
+                container.innerHTML = data.slice().reverse().map(entry => {
+                    const actionName = getActionName(entry.action);
+                    const status = getActionName(entry.status);
+                    const details = JSON.stringify(entry.action, null, 2);
+                    const outcome = entry.status.rejected
+                        ? 'Reason: ' + entry.status.rejected.reason
+                        : entry.status.executed ? entry.status.executed.output : '';
+                    const buttons = status === 'pending' ? `
+                        <button class="expand-toggle" onclick="decide(${entry.id}, 'approve')">Approve</button>
+                        <button class="expand-toggle" onclick="editApproval(${entry.id})">Edit</button>
+                        <button class="expand-toggle" onclick="decide(${entry.id}, 'reject')">Reject</button>
+                    ` : '';
+
+                    return `
+                        <div class="log-entry">
+                            <div class="timestamp">${new Date(entry.created_at).toLocaleString()}</div>
+                            <div class="action-name">#${entry.id} ${actionName} (${status})</div>
+                            <div class="prompt-section">
+                                <div id="approval-${entry.id}" class="prompt-content">${escapeHtml(details)}</div>
+                            </div>
+                            ${outcome ? `<div class="result">${escapeHtml(outcome)}</div>` : ''}
+                            ${buttons}
+                        </div>
+                    `;
+                }).join('');
+            } catch (error) {
+                document.getElementById('approvals').innerHTML = 
+                    '<div class="error">Error loading approval requests: ' + error.message + '</div>';
+            }
+        }
+        
+        async function decide(id, verb) {
+            let body = '';
+            if (verb === 'reject') {
+                const reason = prompt('Why is this action rejected? The agent will see this reason.');
+                if (reason === null) {
+                    return;
+                }
+                body = JSON.stringify({ reason });
+            }
+            await postApproval(id, verb, body);
+        }
+        
+        async function editApproval(id) {
+            const current = document.getElementById(`approval-${id}`).textContent;
+            const edited = prompt('Edit the action JSON:', current);
+            if (edited === null) {
+                return;
+            }
+            await postApproval(id, 'edit', edited);
+        }
+        
+        async function postApproval(id, verb, body) {
+            const response = await fetch(`/api/approvals/${id}/${verb}`, {
+                method: 'POST',
+                headers: adminHeaders(),
+                body
+            });
+            if (!response.ok) {
+                alert(await response.text());
+            }
+            loadApprovals();
+        }
+        
         function getActionName(action) {
             if (typeof action === 'string') {
                 return action;
@@ -418,15 +886,41 @@ impl WebServer {
             }
         }
         
+        async function loadRateLimit() {
+            try {
+                const response = await fetch('/api/rate-limit');
+                const data = await response.json();
+                const container = document.getElementById('rate-limit');
+                if (!data) {
+                    container.textContent = 'GitHub quota: unknown';
+                    return;
+                }
+                const reset = new Date(data.reset).toLocaleTimeString();
+                container.textContent = `GitHub quota: ${data.remaining} of ${data.limit} requests left, resets at ${reset}`;
+                container.classList.toggle('low', data.remaining * 10 <= data.limit);
+            } catch (error) {
+                document.getElementById('rate-limit').textContent =
+                    'Error loading GitHub quota: ' + error.message;
+            }
+        }
+        
         function refreshData() {
+            loadRateLimit();
             if (currentTab === 'actions') {
                 loadActions();
+            } else if (currentTab === 'events') {
+                loadEvents();
+            } else if (currentTab === 'dry-run') {
+                loadDryRun();
+            } else if (currentTab === 'approvals') {
+                loadApprovals();
             } else {
                 loadLLMCalls();
             }
         }
         
         // Initial load
+        loadRateLimit();
         loadActions();
         
         // Auto-refresh every 5 seconds
@@ -444,7 +938,7 @@ impl WebServer {
 
     fn serve_actions(monitor: &Monitor) -> String {
         let actions = monitor.get_action_logs();
-        let json = serde_json::to_string(&actions).unwrap_or_else(|_| "[]".to_string());
+        let json = redact(&serde_json::to_string(&actions).unwrap_or_else(|_| "[]".to_string()));
 
         format!(
             "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
@@ -455,7 +949,40 @@ impl WebServer {
 
     fn serve_llm_calls(monitor: &Monitor) -> String {
         let llm_calls = monitor.get_llm_call_logs();
-        let json = serde_json::to_string(&llm_calls).unwrap_or_else(|_| "[]".to_string());
+        let json = redact(&serde_json::to_string(&llm_calls).unwrap_or_else(|_| "[]".to_string()));
+
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            json.len(),
+            json
+        )
+    }
+
+    fn serve_events(monitor: &Monitor) -> String {
+        let events = monitor.get_event_logs();
+        let json = redact(&serde_json::to_string(&events).unwrap_or_else(|_| "[]".to_string()));
+
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            json.len(),
+            json
+        )
+    }
+
+    fn serve_dry_run(monitor: &Monitor) -> String {
+        let writes = monitor.get_dry_run_logs();
+        let json = redact(&serde_json::to_string(&writes).unwrap_or_else(|_| "[]".to_string()));
+
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            json.len(),
+            json
+        )
+    }
+
+    fn serve_rate_limit(monitor: &Monitor) -> String {
+        let rate_limit = monitor.get_rate_limit();
+        let json = serde_json::to_string(&rate_limit).unwrap_or_else(|_| "null".to_string());
 
         format!(
             "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
@@ -473,3 +1000,78 @@ impl WebServer {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_approvals() {
+        let approvals = ApprovalQueue::new();
+        let id = approvals.submit(Actions::GithubCloseIssue { issue_number: 1 });
+
+        let edit = format!("POST /api/approvals/{}/edit HTTP/1.1\r\n\r\n", id);
+        let response = WebServer::handle_approvals(
+            &approvals,
+            &edit,
+            br#"{"github_close_issue": {"issue_number": 2}}"#,
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        let reject = format!("POST /api/approvals/{}/reject HTTP/1.1\r\n\r\n", id);
+        let response =
+            WebServer::handle_approvals(&approvals, &reject, br#"{"reason": "Still open"}"#);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        // Decided requests can no longer change
+        let approve = format!("POST /api/approvals/{}/approve HTTP/1.1\r\n\r\n", id);
+        let response = WebServer::handle_approvals(&approvals, &approve, b"");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+        let list = WebServer::handle_approvals(&approvals, "GET /api/approvals HTTP/1.1\r\n", b"");
+        assert!(list.contains(r#""issue_number":2"#));
+        assert!(list.contains("Still open"));
+    }
+
+    #[test]
+    fn test_approvals_require_admin_token() {
+        let monitor = Monitor::new();
+        let approvals = ApprovalQueue::new();
+        let list = |head: &str, token: Option<&str>| {
+            WebServer::route(head, b"", &monitor, None, Some(&approvals), token)
+        };
+
+        let anonymous = "GET /api/approvals HTTP/1.1\r\n\r\n";
+        assert!(list(anonymous, None).starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(list(anonymous, Some("s3cret")).starts_with("HTTP/1.1 401 Unauthorized"));
+        let wrong = "GET /api/approvals HTTP/1.1\r\nAuthorization: Bearer guess\r\n\r\n";
+        assert!(list(wrong, Some("s3cret")).starts_with("HTTP/1.1 401 Unauthorized"));
+        let admin = "GET /api/approvals HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n";
+        assert!(list(admin, Some("s3cret")).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn test_body_limit() {
+        let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        let webhook = WebhookReceiver {
+            path: "/webhook".to_string(),
+            secret: "secret".to_string(),
+            sender,
+        };
+        let delivery = "POST /webhook HTTP/1.1\r\nX-GitHub-Event: push\r\nX-Hub-Signature-256: sha256=00\r\n\r\n";
+        assert_eq!(
+            WebServer::body_limit(delivery, Some(&webhook)),
+            MAX_WEBHOOK_BODY_SIZE
+        );
+        let unsigned = "POST /webhook HTTP/1.1\r\nX-GitHub-Event: push\r\n\r\n";
+        assert_eq!(
+            WebServer::body_limit(unsigned, Some(&webhook)),
+            MAX_BODY_SIZE
+        );
+        let approval = "POST /api/approvals/1/edit HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            WebServer::body_limit(approval, Some(&webhook)),
+            MAX_BODY_SIZE
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result, anyhow};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::forge;
+
+/// A GitHub webhook delivery, decoded into the data the agent tracks.
+#[derive(Debug, Clone)]
+pub enum WebhookEvent {
+    Issue(forge::Issue),
+    IssueComment {
+        issue_number: u64,
+        comment_id: u64,
+        author: String,
+        body: String,
+    },
+    Push {
+        branch: String,
+        head_commit: String,
+    },
+    TagPush {
+        tag: String,
+    },
+    /// A release was published, the agent lists releases to find it.
+    Release,
+    PullRequest(forge::PullRequest),
+}
+
+/// Checks the `X-Hub-Signature-256` header against the HMAC-SHA256 of the body.
+pub fn verify_signature(secret: &str, body: &[u8], signature_header: &str) -> bool {
+    let Some(signature) = signature_header.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    // verify_slice compares in constant time
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Decodes a delivery given its `X-GitHub-Event` type.
+/// Returns `None` for event types and actions the agent does not react to.
+pub fn parse_delivery(event_type: &str, body: &[u8]) -> Result<Option<WebhookEvent>> {
+    let payload: Value =
+        serde_json::from_slice(body).context("Failed to parse webhook payload as JSON")?;
+
+    let event = match event_type {
+        "issues" => {
+            let issue: octocrab::models::issues::Issue =
+                serde_json::from_value(payload["issue"].clone())
+                    .context("Failed to decode issue from webhook payload")?;
+            Some(WebhookEvent::Issue(forge::Issue::from(issue)))
+        }
+        "issue_comment" => {
+            if payload["action"] != "created" {
+                return Ok(None);
+            }
+            Some(WebhookEvent::IssueComment {
+                issue_number: payload["issue"]["number"]
+                    .as_u64()
+                    .ok_or_else(|| anyhow!("Missing issue number in webhook payload"))?,
+                comment_id: payload["comment"]["id"]
+                    .as_u64()
+                    .ok_or_else(|| anyhow!("Missing comment id in webhook payload"))?,
+                author: payload["comment"]["user"]["login"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                body: payload["comment"]["body"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+        }
+        "push" => {
+            let reference = payload["ref"].as_str().unwrap_or_default();
+            if let Some(tag) = reference.strip_prefix("refs/tags/") {
+                return Ok(Some(WebhookEvent::TagPush {
+                    tag: tag.to_string(),
+                }));
+            }
+            let Some(branch) = reference.strip_prefix("refs/heads/") else {
+                return Ok(None);
+            };
+            Some(WebhookEvent::Push {
+                branch: branch.to_string(),
+                head_commit: payload["after"].as_str().unwrap_or_default().to_string(),
+            })
+        }
+        "release" if payload["action"] == "published" => Some(WebhookEvent::Release),
+        "pull_request" => {
+            let pr: octocrab::models::pulls::PullRequest =
+                serde_json::from_value(payload["pull_request"].clone())
+                    .context("Failed to decode pull request from webhook payload")?;
+            Some(WebhookEvent::PullRequest(forge::PullRequest::from(pr)))
+        }
+        _ => None,
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        // Example from the GitHub webhook documentation
+        let secret = "It's a Secret to Everybody";
+        let body = b"Hello, World!";
+        let header = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        assert!(verify_signature(secret, body, header));
+        assert!(!verify_signature("wrong secret", body, header));
+        assert!(!verify_signature(secret, b"Hello, World?", header));
+        assert!(!verify_signature(secret, body, "sha1=757107ea"));
+    }
+
+    #[test]
+    fn test_parse_push() {
+        let body = br#"{"ref": "refs/heads/main", "after": "abc123"}"#;
+        match parse_delivery("push", body).unwrap() {
+            Some(WebhookEvent::Push {
+                branch,
+                head_commit,
+            }) => {
+                assert_eq!(branch, "main");
+                assert_eq!(head_commit, "abc123");
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+
+        let tag = br#"{"ref": "refs/tags/v1.0", "after": "abc123"}"#;
+        assert!(matches!(
+            parse_delivery("push", tag).unwrap(),
+            Some(WebhookEvent::TagPush { tag }) if tag == "v1.0"
+        ));
+    }
+
+    #[test]
+    fn test_parse_issue_comment() {
+        let body = br#"{
+            "action": "created",
+            "issue": {"number": 42},
+            "comment": {"id": 7, "user": {"login": "octocat"}, "body": "Still broken"}
+        }"#;
+        match parse_delivery("issue_comment", body).unwrap() {
+            Some(WebhookEvent::IssueComment {
+                issue_number,
+                comment_id,
+                author,
+                body,
+            }) => {
+                assert_eq!(issue_number, 42);
+                assert_eq!(comment_id, 7);
+                assert_eq!(author, "octocat");
+                assert_eq!(body, "Still broken");
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+
+        let edited = br#"{"action": "edited", "issue": {"number": 42}, "comment": {"id": 7}}"#;
+        assert!(parse_delivery("issue_comment", edited).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_unknown_event() {
+        assert!(parse_delivery("ping", b"{}").unwrap().is_none());
+    }
+}